@@ -0,0 +1,66 @@
+//! ARM Data Watchpoint and Trace unit
+//!
+//! Only the cycle counter is supported. It is present on the Cortex-M3 and
+//! Cortex-M4, but not on the Cortex-M0.
+//!
+//! <http://infocenter.arm.com/help/index.jsp?topic=/com.arm.doc.ddi0439b/BABJFFGJ.html>
+
+use kernel::common::cells::VolatileCell;
+use kernel::common::StaticRef;
+use kernel::hil::time::BusyWait;
+
+#[repr(C)]
+struct DwtRegisters {
+    ctrl: VolatileCell<u32>,
+    cyccnt: VolatileCell<u32>,
+}
+
+const DWT: StaticRef<DwtRegisters> = unsafe { StaticRef::new(0xE0001000 as *const DwtRegisters) };
+
+/// Debug Exception and Monitor Control Register, part of the core debug
+/// block. The DWT is only clocked while its TRCENA bit is set.
+const DEMCR: StaticRef<VolatileCell<u32>> =
+    unsafe { StaticRef::new(0xE000EDFC as *const VolatileCell<u32>) };
+
+const DEMCR_TRCENA: u32 = 1 << 24;
+const CTRL_CYCCNTENA: u32 = 1 << 0;
+
+/// Start the cycle counter from zero, unless it is already running.
+pub unsafe fn enable_cycle_counter() {
+    if DWT.ctrl.get() & CTRL_CYCCNTENA != 0 {
+        return;
+    }
+    DEMCR.set(DEMCR.get() | DEMCR_TRCENA);
+    DWT.cyccnt.set(0);
+    DWT.ctrl.set(DWT.ctrl.get() | CTRL_CYCCNTENA);
+}
+
+/// The number of core clock cycles since the counter was enabled. Wraps
+/// around every 2^32 cycles.
+pub fn cycle_count() -> u32 {
+    DWT.cyccnt.get()
+}
+
+/// Microsecond delays timed with the cycle counter.
+pub struct CycleDelay {
+    cycles_per_us: u32,
+}
+
+impl CycleDelay {
+    /// `core_clock_hz` is the frequency of the core clock. Starts the cycle
+    /// counter if it is not already running.
+    pub unsafe fn new(core_clock_hz: u32) -> CycleDelay {
+        enable_cycle_counter();
+        CycleDelay {
+            cycles_per_us: core_clock_hz / 1_000_000,
+        }
+    }
+}
+
+impl BusyWait for CycleDelay {
+    fn delay_us(&self, us: u32) {
+        let start = cycle_count();
+        let cycles = us.saturating_mul(self.cycles_per_us);
+        while cycle_count().wrapping_sub(start) < cycles {}
+    }
+}
@@ -0,0 +1,85 @@
+//! Optional measurement of how long the kernel can delay interrupts and
+//! events.
+//!
+//! With the `latency_stats` feature enabled, `support::atomic()` measures how
+//! long each critical section keeps interrupts disabled, and chips wrap the
+//! servicing of each event in `service_event()`. Both durations are kept in
+//! core clock cycles in stats `Gauge`s, whose `max()` is the worst case seen
+//! so far. Comparing those against deadlines such as a radio receive window
+//! or the time to fill a UART FIFO shows whether a workload can miss them.
+//!
+//! Without the feature nothing is measured and the gauges stay at zero. The
+//! measurements use the DWT cycle counter, so they are not available on the
+//! Cortex-M0.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! cortexm4::latency::enable();
+//! ...
+//! debug!(
+//!     "irqs off: {} cycles, event: {} cycles",
+//!     cortexm4::latency::CRITICAL_SECTION_CYCLES.max(),
+//!     cortexm4::latency::EVENT_SERVICE_CYCLES.max()
+//! );
+//! ```
+
+use core::ops::FnOnce;
+use dwt;
+use kernel::common::stats::{Atomic, Gauge};
+use support::{self, Primask};
+
+/// Critical sections for a gauge that is only updated while interrupts are
+/// already disabled.
+pub struct Masked;
+
+impl Atomic for Masked {
+    unsafe fn atomic<F: FnOnce() -> R, R>(f: F) -> R {
+        f()
+    }
+}
+
+/// Cycles spent with interrupts disabled by `support::atomic()`.
+pub static mut CRITICAL_SECTION_CYCLES: Gauge<Masked> = Gauge::new();
+
+/// Cycles spent servicing a single event.
+pub static mut EVENT_SERVICE_CYCLES: Gauge<Primask> = Gauge::new();
+
+/// Start the cycle counter the measurements are based on. Boards call this
+/// once during start-up, before any measurement is taken.
+pub unsafe fn enable() {
+    if cfg!(feature = "latency_stats") {
+        dwt::enable_cycle_counter();
+    }
+}
+
+/// Forget the worst cases seen so far, for example to measure a new phase
+/// of a workload.
+pub unsafe fn reset() {
+    support::atomic(|| CRITICAL_SECTION_CYCLES.reset_max());
+    EVENT_SERVICE_CYCLES.reset_max();
+}
+
+/// Service an event with `f`, recording how long it took.
+#[inline(always)]
+pub fn service_event<F: FnOnce() -> R, R>(f: F) -> R {
+    if cfg!(feature = "latency_stats") {
+        let start = dwt::cycle_count();
+        let res = f();
+        unsafe {
+            EVENT_SERVICE_CYCLES.set(dwt::cycle_count().wrapping_sub(start));
+        }
+        res
+    } else {
+        f()
+    }
+}
+
+/// Record the length of a critical section that started at cycle `start`.
+/// Must be called before interrupts are enabled again.
+#[cfg(target_os = "none")]
+#[inline(always)]
+crate unsafe fn record_critical_section(start: u32) {
+    CRITICAL_SECTION_CYCLES.set(dwt::cycle_count().wrapping_sub(start));
+}
@@ -2,12 +2,14 @@
 
 #![crate_name = "cortexm"]
 #![crate_type = "rlib"]
-#![feature(asm, const_fn, lang_items, used)]
+#![feature(asm, const_fn, crate_visibility_modifier, lang_items, used)]
 #![no_std]
 
 #[macro_use(register_bitfields, register_bitmasks)]
 extern crate kernel;
 
+pub mod dwt;
+pub mod latency;
 pub mod nvic;
 pub mod scb;
 pub mod support;
@@ -17,6 +17,12 @@ struct NvicRegisters {
     _reserved3: [VolatileCell<u32>; 24],
     // Interrupt clear-pending (and read pending state)
     icpr: [VolatileCell<u32>; 8],
+    _reserved4: [VolatileCell<u32>; 24],
+    // Interrupt active bit
+    iabr: [VolatileCell<u32>; 8],
+    _reserved5: [VolatileCell<u32>; 56],
+    // Interrupt priority, one byte per interrupt
+    ipr: [VolatileCell<u8>; 240],
 }
 
 // NVIC base address
@@ -102,6 +108,14 @@ impl Nvic {
         nvic.icer[idx / 32].set(1 << (self.0 & 31));
     }
 
+    /// Set pending state, as if the peripheral had raised the interrupt
+    pub fn set_pending(&self) {
+        let nvic: &NvicRegisters = &*NVIC_BASE_ADDRESS;
+        let idx = self.0 as usize;
+
+        nvic.ispr[idx / 32].set(1 << (self.0 & 31));
+    }
+
     /// Clear pending state
     pub fn clear_pending(&self) {
         let nvic: &NvicRegisters = &*NVIC_BASE_ADDRESS;
@@ -109,4 +123,14 @@ impl Nvic {
 
         nvic.icpr[idx / 32].set(1 << (self.0 & 31));
     }
+
+    /// Set the priority of the interrupt. Lower values preempt higher
+    /// values. Chips only implement the most significant bits of the
+    /// priority, so e.g. on a chip with three priority bits the usable
+    /// priorities are 0x00, 0x20, ..., 0xE0.
+    pub fn set_priority(&self, priority: u8) {
+        let nvic: &NvicRegisters = &*NVIC_BASE_ADDRESS;
+
+        nvic.ipr[self.0 as usize].set(priority);
+    }
 }
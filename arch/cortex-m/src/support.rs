@@ -1,4 +1,5 @@
 use core::ops::FnOnce;
+use kernel::common::stats;
 
 #[cfg(target_os = "none")]
 #[inline(always)]
@@ -40,13 +41,30 @@ where
     // Set PRIMASK
     asm!("cpsid i" :::: "volatile");
 
-    let res = f();
+    let res = if cfg!(feature = "latency_stats") {
+        let start = ::dwt::cycle_count();
+        let res = f();
+        ::latency::record_critical_section(start);
+        res
+    } else {
+        f()
+    };
 
     // Unset PRIMASK
     asm!("cpsie i" :::: "volatile");
     return res;
 }
 
+/// Critical sections for `kernel::common::stats` types, implemented with
+/// `atomic()`.
+pub struct Primask;
+
+impl stats::Atomic for Primask {
+    unsafe fn atomic<F: FnOnce() -> R, R>(f: F) -> R {
+        atomic(f)
+    }
+}
+
 #[cfg(target_os = "none")]
 pub unsafe fn atomic_write(location: &mut u64, value: u64) {
     atomic(|| ::core::ptr::write_volatile(location, value));
@@ -1,5 +1,61 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Board options that may be overridden from the environment at build time,
+/// along with their defaults. See `src/config.rs`.
+const OPTIONS: [(&str, &str, usize); 3] = [
+    ("NUM_PROCS", "usize", 2),
+    ("APP_MEMORY_SIZE", "usize", 0xA000),
+    ("STACK_SIZE", "usize", 0x1000),
+];
+
+fn parse(name: &str, value: &str) -> usize {
+    let parsed = if value.starts_with("0x") || value.starts_with("0X") {
+        usize::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse()
+    };
+    parsed.unwrap_or_else(|_| panic!("{} is not a number: {:?}", name, value))
+}
+
+/// The size of the `ram` region in the linker script, which is given as
+/// `LENGTH = <hex or decimal>[K]`.
+fn ram_size() -> usize {
+    let layout = fs::read_to_string("chip_layout.ld").unwrap();
+    let line = layout
+        .lines()
+        .find(|line| line.trim_left().starts_with("ram"))
+        .expect("no ram region in chip_layout.ld");
+    let length = line
+        .split("LENGTH")
+        .nth(1)
+        .and_then(|rest| rest.split('=').nth(1))
+        .expect("ram region has no LENGTH")
+        .trim();
+    if length.ends_with('K') {
+        parse("RAM", &length[..length.len() - 1]) * 1024
+    } else {
+        parse("RAM", length)
+    }
+}
+
 fn main() {
     println!("cargo:rerun-if-changed=layout.ld");
     println!("cargo:rerun-if-changed=chip_layout.ld");
     println!("cargo:rerun-if-changed=../kernel_layout.ld");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let mut out = File::create(Path::new(&out_dir).join("config.rs")).unwrap();
+    for &(name, ty, default) in OPTIONS.iter() {
+        let var = format!("LAUNCHXL_{}", name);
+        println!("cargo:rerun-if-env-changed={}", var);
+        let value = match env::var(&var) {
+            Ok(value) => parse(&var, value.trim()),
+            Err(_) => default,
+        };
+        writeln!(out, "pub const {}: {} = {:#x};", name, ty, value).unwrap();
+    }
+    writeln!(out, "pub const RAM_SIZE: usize = {:#x};", ram_size()).unwrap();
 }
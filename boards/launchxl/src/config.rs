@@ -0,0 +1,22 @@
+//! Build-time configuration for the launchxl board.
+//!
+//! These values size the process table, the RAM dedicated to applications
+//! and the kernel stack. Downstream products can tune them without touching
+//! `main.rs` by setting the matching environment variable when building, for
+//! example:
+//!
+//! ```bash
+//! $> LAUNCHXL_NUM_PROCS=4 LAUNCHXL_APP_MEMORY_SIZE=0xC000 make
+//! ```
+//!
+//! | Variable                   | Default  |
+//! |----------------------------|----------|
+//! | `LAUNCHXL_NUM_PROCS`       | 2        |
+//! | `LAUNCHXL_APP_MEMORY_SIZE` | `0xA000` |
+//! | `LAUNCHXL_STACK_SIZE`      | `0x1000` |
+//!
+//! The application memory is placed at the end of RAM by the linker script.
+//! `RAM_SIZE` is read from `chip_layout.ld`, and the build fails if the
+//! application memory and stack together do not fit in it.
+
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
@@ -7,6 +7,8 @@ use kernel::hil::led;
 use kernel::hil::uart::{self, UART};
 
 use PROCESSES;
+#[cfg(feature = "syscall_trace")]
+use SYSCALL_TRACE;
 
 struct Writer {
     initialized: bool,
@@ -43,5 +45,11 @@ pub unsafe extern "C" fn panic_fmt(pi: &PanicInfo) -> ! {
 
     let led = &mut led::LedLow::new(&mut cc26x2::gpio::PORT[LED_PIN]);
     let writer = &mut WRITER;
-    debug::panic(&mut [led], writer, pi, &cortexm4::support::nop, &PROCESSES)
+    debug::panic_begin(&cortexm4::support::nop);
+    debug::panic_banner(writer, pi);
+    debug::flush(writer);
+    debug::panic_process_info(&PROCESSES, writer);
+    #[cfg(feature = "syscall_trace")]
+    SYSCALL_TRACE.map(|trace| trace.fmt(writer));
+    debug::panic_blink_forever(&mut [led])
 }
@@ -9,15 +9,18 @@ extern crate enum_primitive;
 extern crate cc26x2;
 
 #[allow(unused_imports)]
-#[macro_use(create_capability, debug, debug_gpio, static_init)]
+#[macro_use(create_capability, debug, debug_gpio, declare_app_memory, static_init)]
 extern crate kernel;
 
 use capsules::virtual_uart::{UartDevice, UartMux};
 use cc26x2::aon;
+use cc26x2::gpio::WakeupLevel;
+use cc26x2::peripheral_interrupts::NVIC_IRQ;
 use cc26x2::prcm;
 use kernel::capabilities;
 use kernel::hil;
 use kernel::hil::entropy::Entropy32;
+use kernel::hil::gpio::PinCtl;
 use kernel::hil::i2c::I2CMaster;
 use kernel::hil::rng::Rng;
 use kernel::Chip;
@@ -25,26 +28,42 @@ use kernel::Chip;
 #[macro_use]
 pub mod io;
 
+mod config;
+
 #[allow(dead_code)]
 mod i2c_tests;
 #[allow(dead_code)]
 mod uart_echo;
 
-// How should the kernel respond when a process faults.
-const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
-
-// Number of concurrent processes this platform supports.
-const NUM_PROCS: usize = 2;
-static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] = [None, None];
-
-#[link_section = ".app_memory"]
-// Give half of RAM to be dedicated APP memory
-static mut APP_MEMORY: [u8; 0xA000] = [0; 0xA000];
+// Processes, their memory and the kernel stack are sized in `config`. By
+// default half of RAM is dedicated to APP memory.
+declare_app_memory! {
+    processes: config::NUM_PROCS,
+    app_memory: config::APP_MEMORY_SIZE,
+    stack: config::STACK_SIZE,
+    fault_response: kernel::procs::FaultResponse::Panic,
+    ram: config::RAM_SIZE,
+}
 
-/// Dummy buffer that causes the linker to reserve enough space for the stack.
-#[no_mangle]
-#[link_section = ".stack_buffer"]
-pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+// Recent system calls made by processes, printed if the kernel panics.
+#[cfg(feature = "syscall_trace")]
+static mut SYSCALL_TRACE: Option<&'static kernel::syscall_trace::SyscallTrace> = None;
+
+/// AON events that wake the MCU from standby: the alarm's RTC channel and the
+/// buttons.
+const WAKEUP_SOURCES: [aon::WakeupSource; 2] =
+    [aon::WakeupSource::RtcCh1, aon::WakeupSource::IoEdge];
+
+/// Interrupt priorities for this board. The RTC and GPIO interrupts only set
+/// an event flag, so they are allowed to preempt the serial handlers, which
+/// can run for a long time while draining a FIFO.
+const NVIC_PRIORITIES: [(NVIC_IRQ, u8); 5] = [
+    (NVIC_IRQ::AON_RTC, 0x40),
+    (NVIC_IRQ::GPIO, 0x60),
+    (NVIC_IRQ::UART0, 0x80),
+    (NVIC_IRQ::UART1, 0x80),
+    (NVIC_IRQ::I2C0, 0x80),
+];
 
 pub struct Platform {
     gpio: &'static capsules::gpio::GPIO<'static, cc26x2::gpio::GPIOPin>,
@@ -57,6 +76,8 @@ pub struct Platform {
     >,
     rng: &'static capsules::rng::RngDriver<'static>,
     i2c_master: &'static capsules::i2c_master::I2CMasterDriver<cc26x2::i2c::I2CMaster<'static>>,
+    senml: &'static capsules::senml::SenmlDriver,
+    power: &'static capsules::power::PowerDriver<'static, aon::Aon>,
 }
 
 impl kernel::Platform for Platform {
@@ -72,6 +93,8 @@ impl kernel::Platform for Platform {
             capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
             capsules::i2c_master::DRIVER_NUM => f(Some(self.i2c_master)),
+            capsules::senml::DRIVER_NUM => f(Some(self.senml)),
+            capsules::power::DRIVER_NUM => f(Some(self.power)),
             _ => f(None),
         }
     }
@@ -92,13 +115,29 @@ unsafe fn configure_pins() {
 
     cc26x2::gpio::PORT[PIN_FN::BUTTON_1 as usize].enable_gpio();
     cc26x2::gpio::PORT[PIN_FN::BUTTON_2 as usize].enable_gpio();
+    // Hysteresis on the button inputs helps reject slow, noisy edges.
+    cc26x2::gpio::PORT[PIN_FN::BUTTON_1 as usize].set_hysteresis(true);
+    cc26x2::gpio::PORT[PIN_FN::BUTTON_2 as usize].set_hysteresis(true);
+    // Pressing button 1 wakes the board from shutdown. This must survive
+    // the button capsule setting the pin up as an input later on, which
+    // enable_gpio() takes care of by keeping WU_CFG.
+    cc26x2::gpio::PORT[PIN_FN::BUTTON_1 as usize].enable_shutdown_wakeup(WakeupLevel::Low);
 
     cc26x2::gpio::PORT[PIN_FN::GPIO0 as usize].enable_gpio();
 }
 
+/// Only the first process in flash may shut the board down.
+fn shutdown_allowed(app: kernel::AppId) -> bool {
+    app.idx() == 0
+}
+
 #[no_mangle]
 pub unsafe fn reset_handler() {
     cc26x2::init();
+    cc26x2::peripheral_interrupts::set_priorities(&NVIC_PRIORITIES);
+
+    // Only starts measuring when built with the `latency_stats` feature.
+    cortexm4::latency::enable();
 
     // Create capabilities that the board needs to call certain protected kernel
     // functions.
@@ -106,9 +145,11 @@ pub unsafe fn reset_handler() {
         create_capability!(capabilities::ProcessManagementCapability);
     let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
     let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+    let power_management_capability = create_capability!(capabilities::PowerManagementCapability);
 
     // Setup AON event defaults
     aon::AON.setup();
+    aon::AON.set_wakeup_sources(&WAKEUP_SOURCES);
 
     // Power on peripherals (eg. GPIO)
     prcm::Power::enable_domain(prcm::PowerDomain::Peripherals);
@@ -118,11 +159,26 @@ pub unsafe fn reset_handler() {
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
+    #[cfg(feature = "syscall_trace")]
+    {
+        let syscall_trace = static_init!(
+            kernel::syscall_trace::SyscallTrace,
+            kernel::syscall_trace::SyscallTrace::new(&mut kernel::syscall_trace::TRACE_BUF)
+        );
+        board_kernel.set_syscall_trace(syscall_trace, &process_management_capability);
+        SYSCALL_TRACE = Some(syscall_trace);
+    }
+
     // Enable the GPIO clocks
     prcm::Clock::enable_gpio();
 
     configure_pins();
 
+    // Pads stay latched at their pre-shutdown levels after waking from
+    // shutdown until they are released, so release them only once the pins
+    // have been configured again. This is a no-op after a cold boot.
+    aon::AON.io_freeze(false);
+
     // LEDs
     let led_pins = static_init!(
         [(
@@ -182,6 +238,9 @@ pub unsafe fn reset_handler() {
         )
     );
     hil::uart::UART::set_client(&cc26x2::uart::UART0, uart_mux);
+    // Split long console writes so that kernel debug output is not held up
+    // behind them.
+    uart_mux.set_chunk_buffer(&mut capsules::virtual_uart::TX_CHUNK_BUF);
 
     // Create a UartDevice for the console.
     let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
@@ -205,6 +264,7 @@ pub unsafe fn reset_handler() {
     // Create virtual device for kernel debug.
     let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
     debugger_uart.setup();
+    debugger_uart.set_priority(true);
     let debugger = static_init!(
         kernel::debug::DebugWriter,
         kernel::debug::DebugWriter::new(
@@ -291,6 +351,20 @@ pub unsafe fn reset_handler() {
     cc26x2::trng::TRNG.set_client(entropy_to_random);
     entropy_to_random.set_client(rng);
 
+    let senml = static_init!(
+        capsules::senml::SenmlDriver,
+        capsules::senml::SenmlDriver::new(board_kernel.create_grant(&memory_allocation_capability))
+    );
+
+    let power = static_init!(
+        capsules::power::PowerDriver<'static, aon::Aon>,
+        capsules::power::PowerDriver::new(
+            &aon::AON,
+            shutdown_allowed,
+            &power_management_capability
+        )
+    );
+
     let launchxl = Platform {
         console,
         gpio,
@@ -299,6 +373,8 @@ pub unsafe fn reset_handler() {
         alarm,
         rng,
         i2c_master,
+        senml,
+        power,
     };
 
     let chip = static_init!(cc26x2::chip::Cc26X2, cc26x2::chip::Cc26X2::new());
@@ -13,6 +13,20 @@ pub struct App {
 
 pub static mut BUF: [u8; 64] = [0; 64];
 
+/// The high bits of the first address byte of a 10-bit address. The two most
+/// significant bits of the address follow, and the remaining eight are sent
+/// as the first data byte.
+const TEN_BIT_ADDRESS_PREFIX: u8 = 0b1111_0000;
+
+/// Split a 10-bit address into the 7-bit address to give the controller and
+/// the byte sent before any data.
+fn ten_bit_address(addr: u16) -> (u8, u8) {
+    (
+        (TEN_BIT_ADDRESS_PREFIX >> 1) | ((addr >> 8) as u8 & 0b11),
+        addr as u8,
+    )
+}
+
 struct Transaction {
     /// The buffer containing the bytes to transmit as it should be returned to
     /// the client
@@ -43,7 +57,7 @@ impl<I: 'static + i2c::I2CMaster> I2CMasterDriver<I> {
         app_id: AppId,
         app: &mut App,
         command: CMD,
-        addr: u8,
+        addr: u16,
         wlen: u8,
         rlen: u8,
     ) -> ReturnCode {
@@ -51,8 +65,19 @@ impl<I: 'static + i2c::I2CMaster> I2CMasterDriver<I> {
             .enter(app_id, |_, _| {
                 if let Some(app_buffer) = app.slice.take() {
                     self.buf.take().map(|buffer| {
+                        // A 10-bit address is sent as a 7-bit address
+                        // followed by the low address byte, which goes in
+                        // front of the data.
+                        let (addr, offset) = match command {
+                            CMD::WRITE_10BIT | CMD::READ_10BIT | CMD::WRITE_READ_10BIT => {
+                                let (addr, low_byte) = ten_bit_address(addr);
+                                buffer[0] = low_byte;
+                                (addr, 1)
+                            }
+                            _ => (addr as u8, 0),
+                        };
                         for n in 0..wlen as usize {
-                            buffer[n] = app_buffer.as_ref()[n];
+                            buffer[n + offset] = app_buffer.as_ref()[n];
                         }
 
                         let read_len: OptionalCell<usize>;
@@ -69,6 +94,13 @@ impl<I: 'static + i2c::I2CMaster> I2CMasterDriver<I> {
                             CMD::WRITE => self.i2c.write(addr, buffer, wlen),
                             CMD::READ => self.i2c.read(addr, buffer, rlen),
                             CMD::WRITE_READ => self.i2c.write_read(addr, buffer, wlen, rlen),
+                            CMD::WRITE_10BIT => self.i2c.write(addr, buffer, wlen + 1),
+                            // Reading from a 10-bit address writes the low
+                            // address byte, then reads after a repeated start
+                            CMD::READ_10BIT => self.i2c.write_read(addr, buffer, 1, rlen),
+                            CMD::WRITE_READ_10BIT => {
+                                self.i2c.write_read(addr, buffer, wlen + 1, rlen)
+                            }
                         }
                         return ReturnCode::SUCCESS;
                     });
@@ -94,6 +126,9 @@ pub enum CMD {
     WRITE = 1,
     READ = 2,
     WRITE_READ = 3,
+    WRITE_10BIT = 4,
+    READ_10BIT = 5,
+    WRITE_READ_10BIT = 6,
 }
 }
 
@@ -143,6 +178,18 @@ impl<I: i2c::I2CMaster> Driver for I2CMasterDriver<I> {
     }
 
     /// Initiate transfers
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Write `arg2` bytes to the 7-bit address `arg1`.
+    /// - `2`: Read `arg2` bytes from the 7-bit address `arg1`.
+    /// - `3`: Write bits 8-15 of `arg1` bytes to the 7-bit address in bits
+    ///        0-7, then read `arg2` bytes after a repeated start.
+    /// - `4`: Write `arg2` bytes to the 10-bit address `arg1`.
+    /// - `5`: Read `arg2` bytes from the 10-bit address `arg1`.
+    /// - `6`: Write bits 16-23 of `arg1` bytes to the 10-bit address in bits
+    ///        0-9, then read `arg2` bytes after a repeated start.
     fn command(&self, cmd_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
         if let Some(cmd) = CMD::from_usize(cmd_num) {
             match cmd {
@@ -152,7 +199,7 @@ impl<I: i2c::I2CMaster> Driver for I2CMasterDriver<I> {
                     .enter(appid, |app, _| {
                         let addr = arg1 as u8;
                         let write_len = arg2;
-                        self.operation(appid, app, CMD::WRITE, addr, write_len as u8, 0);
+                        self.operation(appid, app, CMD::WRITE, addr as u16, write_len as u8, 0);
                         ReturnCode::SUCCESS
                     }).unwrap_or_else(|err| err.into()),
                 CMD::READ => self
@@ -160,7 +207,7 @@ impl<I: i2c::I2CMaster> Driver for I2CMasterDriver<I> {
                     .enter(appid, |app, _| {
                         let addr = arg1 as u8;
                         let read_len = arg2;
-                        self.operation(appid, app, CMD::READ, addr, 0, read_len as u8);
+                        self.operation(appid, app, CMD::READ, addr as u16, 0, read_len as u8);
                         ReturnCode::SUCCESS
                     }).unwrap_or_else(|err| err.into()),
                 CMD::WRITE_READ => {
@@ -173,13 +220,30 @@ impl<I: i2c::I2CMaster> Driver for I2CMasterDriver<I> {
                                 appid,
                                 app,
                                 CMD::WRITE_READ,
-                                addr,
+                                addr as u16,
                                 write_len as u8,
                                 read_len as u8,
                             );
                             ReturnCode::SUCCESS
                         }).unwrap_or_else(|err| err.into())
                 }
+                CMD::WRITE_10BIT | CMD::READ_10BIT | CMD::WRITE_READ_10BIT => {
+                    let addr = (arg1 & 0x3ff) as u16;
+                    let (write_len, read_len) = match cmd {
+                        CMD::WRITE_10BIT => (arg2, 0),
+                        CMD::READ_10BIT => (0, arg2),
+                        _ => ((arg1 >> 16) & 0xff, arg2),
+                    };
+                    // The low address byte takes up one byte of the buffer
+                    if write_len >= self.buf.map_or(255, |buf| buf.len()) {
+                        return ReturnCode::ESIZE;
+                    }
+                    self.apps
+                        .enter(appid, |app, _| {
+                            self.operation(appid, app, cmd, addr, write_len as u8, read_len as u8);
+                            ReturnCode::SUCCESS
+                        }).unwrap_or_else(|err| err.into())
+                }
             }
         } else {
             ReturnCode::ENOSUPPORT
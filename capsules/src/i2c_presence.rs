@@ -0,0 +1,275 @@
+//! Detect which devices are present on an I2C bus.
+//!
+//! `scan()` probes every 7-bit address that the I2C specification does not
+//! reserve and reports the addresses that responded. Capsules can also
+//! register a `Watch` on the address of a device they expect. While any watch
+//! is registered the watched addresses are re-probed periodically, and each
+//! watch's client is told when its device starts or stops responding. This
+//! lets drivers for sensors that are attached or removed in the field notice
+//! the change.
+//!
+//! A device counts as present if it acknowledges its address for a one byte
+//! read. Write-only devices that refuse reads show up as absent.
+//!
+//! The service issues transactions directly on the I2C master, so it must be
+//! the master's only client, in the same way as `i2c_master`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let presence_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let presence = static_init!(
+//!     capsules::i2c_presence::I2CPresence<'static, VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>>,
+//!     capsules::i2c_presence::I2CPresence::new(
+//!         &cc26x2::i2c::I2C0,
+//!         presence_alarm,
+//!         &mut capsules::i2c_presence::BUF,
+//!         1000
+//!     )
+//! );
+//! cc26x2::i2c::I2C0.set_client(presence);
+//! presence_alarm.set_client(presence);
+//!
+//! let sensor_watch = static_init!(
+//!     capsules::i2c_presence::Watch<'static>,
+//!     capsules::i2c_presence::Watch::new(0x40)
+//! );
+//! sensor_watch.set_client(sensor);
+//! presence.watch(sensor_watch);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::i2c::{self, Error};
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+pub static mut BUF: [u8; 1] = [0; 1];
+
+/// Addresses below and above these are reserved by the I2C specification.
+const FIRST_ADDRESS: u8 = 0x08;
+const LAST_ADDRESS: u8 = 0x77;
+
+/// Told when a watched device appears on or disappears from the bus.
+pub trait PresenceClient {
+    fn presence_changed(&self, addr: u8, present: bool);
+}
+
+/// Told when a full scan of the bus has finished.
+pub trait ScanClient {
+    /// Bit `n` of `devices` is set if a device responded at address `n`.
+    fn scan_done(&self, devices: u128);
+}
+
+/// A device address that a capsule expects to find on the bus.
+pub struct Watch<'a> {
+    addr: u8,
+    /// `None` until the address has been probed.
+    present: Cell<Option<bool>>,
+    client: OptionalCell<&'a PresenceClient>,
+    next: ListLink<'a, Watch<'a>>,
+}
+
+impl Watch<'a> {
+    /// Panics if `addr` is above `LAST_ADDRESS`.
+    pub fn new(addr: u8) -> Watch<'a> {
+        if addr > LAST_ADDRESS {
+            panic!("I2CPresence: watched address {:#x} is out of range", addr);
+        }
+        Watch {
+            addr: addr,
+            present: Cell::new(None),
+            client: OptionalCell::empty(),
+            next: ListLink::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a PresenceClient) {
+        self.client.set(client);
+    }
+
+    /// Whether the device responded when it was last probed. Devices are
+    /// assumed absent until probed for the first time.
+    pub fn is_present(&self) -> bool {
+        self.present.get().unwrap_or(false)
+    }
+}
+
+impl ListNode<'a, Watch<'a>> for Watch<'a> {
+    fn next(&'a self) -> &'a ListLink<'a, Watch<'a>> {
+        &self.next
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Scanning,
+    Reprobing,
+}
+
+pub struct I2CPresence<'a, A: time::Alarm> {
+    i2c: &'a i2c::I2CMaster,
+    alarm: &'a A,
+    /// Time between re-probes of watched devices, zero to only probe them
+    /// during scans.
+    interval_ms: Cell<u32>,
+    buffer: TakeCell<'static, [u8]>,
+    state: Cell<State>,
+    /// Addresses still to be probed in the current pass.
+    pending: Cell<u128>,
+    /// Addresses that responded during the current scan.
+    found: Cell<u128>,
+    /// The address being probed.
+    current: Cell<u8>,
+    watches: List<'a, Watch<'a>>,
+    scan_client: OptionalCell<&'a ScanClient>,
+}
+
+impl<A: time::Alarm> I2CPresence<'a, A> {
+    pub fn new(
+        i2c: &'a i2c::I2CMaster,
+        alarm: &'a A,
+        buffer: &'static mut [u8],
+        interval_ms: u32,
+    ) -> I2CPresence<'a, A> {
+        I2CPresence {
+            i2c: i2c,
+            alarm: alarm,
+            interval_ms: Cell::new(interval_ms),
+            buffer: TakeCell::new(buffer),
+            state: Cell::new(State::Idle),
+            pending: Cell::new(0),
+            found: Cell::new(0),
+            current: Cell::new(0),
+            watches: List::new(),
+            scan_client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_scan_client(&self, client: &'a ScanClient) {
+        self.scan_client.set(client);
+    }
+
+    /// Change how often watched devices are re-probed. Zero stops periodic
+    /// re-probing once the current interval has elapsed.
+    pub fn set_interval(&self, interval_ms: u32) {
+        self.interval_ms.set(interval_ms);
+        self.schedule_reprobe();
+    }
+
+    /// Start tracking `watch`. Its client is told whether the device is
+    /// present after the next probe.
+    pub fn watch(&self, watch: &'a Watch<'a>) {
+        self.watches.push_head(watch);
+        if self.state.get() == State::Idle && !self.alarm.is_armed() {
+            self.reprobe();
+        }
+    }
+
+    /// Probe every unreserved address. The scan client is called with the
+    /// result when done.
+    pub fn scan(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        let mut addresses = 0;
+        for addr in FIRST_ADDRESS..LAST_ADDRESS + 1 {
+            addresses |= 1 << addr;
+        }
+        self.found.set(0);
+        self.start_pass(State::Scanning, addresses);
+        ReturnCode::SUCCESS
+    }
+
+    fn reprobe(&self) {
+        let addresses = self
+            .watches
+            .iter()
+            .fold(0, |addresses, watch| addresses | 1 << watch.addr);
+        self.start_pass(State::Reprobing, addresses);
+    }
+
+    fn start_pass(&self, state: State, addresses: u128) {
+        self.state.set(state);
+        self.pending.set(addresses);
+        self.i2c.enable();
+        self.probe_next();
+    }
+
+    fn probe_next(&self) {
+        let pending = self.pending.get();
+        if pending == 0 {
+            self.finish_pass();
+            return;
+        }
+        let addr = pending.trailing_zeros() as u8;
+        self.pending.set(pending & !(1 << addr));
+        self.current.set(addr);
+        self.buffer
+            .take()
+            .map(|buffer| self.i2c.read(addr, buffer, 1));
+    }
+
+    fn finish_pass(&self) {
+        self.i2c.disable();
+        let state = self.state.get();
+        self.state.set(State::Idle);
+        if state == State::Scanning {
+            let found = self.found.get();
+            self.scan_client.map(|client| client.scan_done(found));
+        }
+        self.schedule_reprobe();
+    }
+
+    fn schedule_reprobe(&self) {
+        let interval_ms = self.interval_ms.get();
+        if interval_ms == 0 || self.watches.head().is_none() || self.alarm.is_armed() {
+            return;
+        }
+        let interval = (interval_ms as u64 * <A::Frequency>::frequency() as u64 / 1000) as u32;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+}
+
+impl<A: time::Alarm> time::Client for I2CPresence<'a, A> {
+    fn fired(&self) {
+        // A scan in progress probes the watched addresses as well, and
+        // reschedules the next re-probe when it finishes.
+        if self.state.get() == State::Idle {
+            self.reprobe();
+        }
+    }
+}
+
+impl<A: time::Alarm> i2c::I2CHwMasterClient for I2CPresence<'a, A> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: Error) {
+        self.buffer.replace(buffer);
+        let addr = self.current.get();
+
+        // Losing arbitration says nothing about the device, so its state is
+        // left as it was.
+        if error != Error::ArbitrationLost {
+            let present = error != Error::AddressNak;
+            if present && self.state.get() == State::Scanning {
+                self.found.set(self.found.get() | 1 << addr);
+            }
+            for watch in self.watches.iter().filter(|watch| watch.addr == addr) {
+                if watch.present.get() != Some(present) {
+                    watch.present.set(Some(present));
+                    watch
+                        .client
+                        .map(|client| client.presence_changed(addr, present));
+                }
+            }
+        }
+
+        self.probe_next();
+    }
+}
@@ -33,6 +33,7 @@ pub mod gpio;
 pub mod gpio_async;
 pub mod humidity;
 pub mod i2c_master;
+pub mod i2c_presence;
 pub mod i2c_master_slave_driver;
 pub mod ieee802154;
 pub mod isl29035;
@@ -46,13 +47,17 @@ pub mod ninedof;
 pub mod nonvolatile_storage_driver;
 pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
+pub mod one_wire;
 pub mod pca9544a;
+pub mod power;
 pub mod rf233;
 pub mod rf233_const;
 pub mod rng;
 pub mod sdcard;
 pub mod segger_rtt;
+pub mod senml;
 pub mod si7021;
+pub mod soft_i2c;
 pub mod spi;
 pub mod temperature;
 pub mod tmp006;
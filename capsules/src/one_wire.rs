@@ -0,0 +1,221 @@
+//! Bit-banged 1-Wire bus master.
+//!
+//! Drives a 1-Wire bus, such as a chain of DS18B20 temperature probes, from a
+//! single GPIO pin. The line is driven open-drain: it is pulled low by making
+//! the pin an output at zero, and released by making it an input. It
+//! therefore needs an external pull-up resistor (typically 4.7k).
+//!
+//! Timing follows the standard speed slots from Maxim application note 126.
+//! Every operation busy-waits for its whole duration, and so does every
+//! syscall: a reset blocks the kernel for about 1ms, and reading or writing a
+//! byte for about 0.6ms.
+//!
+//! An interrupt during a slot would stretch it, and the device would then
+//! read or write the wrong bit. Each bit slot, and the presence detection of
+//! a reset, therefore runs with interrupts disabled through the function the
+//! board passes to `new()`, for up to 70us at a time. Noise on the bus can
+//! still corrupt data, so users should check the CRC that 1-Wire devices send
+//! with their data with `crc8()`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! fn one_wire_atomic(f: &mut FnMut()) {
+//!     unsafe { cortexm4::support::atomic(|| f()) }
+//! }
+//!
+//! let delay = static_init!(
+//!     cortexm4::dwt::CycleDelay,
+//!     cortexm4::dwt::CycleDelay::new(48_000_000)
+//! );
+//! let one_wire = static_init!(
+//!     capsules::one_wire::OneWire<'static, cortexm4::dwt::CycleDelay>,
+//!     capsules::one_wire::OneWire::new(&cc26x2::gpio::PORT[21], delay, one_wire_atomic)
+//! );
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! All commands are synchronous.
+//!
+//! - `0`: Driver check.
+//! - `1`: Reset the bus. Returns `ENODEVICE` if no device answered with a
+//!        presence pulse.
+//! - `2`: Write the byte in `arg1`.
+//! - `3`: Read a byte, returned as the value of `SuccessWithValue`.
+
+use kernel::hil::gpio;
+use kernel::hil::time::BusyWait;
+use kernel::{AppId, Driver, ReturnCode};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x80006;
+
+/// Commands understood by every 1-Wire device, sent after a reset.
+pub const READ_ROM: u8 = 0x33;
+pub const MATCH_ROM: u8 = 0x55;
+pub const SKIP_ROM: u8 = 0xCC;
+
+pub struct OneWire<'a, D: BusyWait + 'a> {
+    pin: &'a gpio::Pin,
+    delay: &'a D,
+    /// Runs the given closure with interrupts disabled.
+    atomic: fn(&mut FnMut()),
+}
+
+impl<D: BusyWait> OneWire<'a, D> {
+    pub fn new(pin: &'a gpio::Pin, delay: &'a D, atomic: fn(&mut FnMut())) -> OneWire<'a, D> {
+        pin.make_input();
+        OneWire {
+            pin: pin,
+            delay: delay,
+            atomic: atomic,
+        }
+    }
+
+    fn pull_low(&self) {
+        self.pin.clear();
+        self.pin.make_output();
+    }
+
+    fn release(&self) {
+        self.pin.make_input();
+    }
+
+    /// Send a reset pulse. Returns whether any device answered with a
+    /// presence pulse.
+    pub fn reset(&self) -> bool {
+        // Only the minimum length of the reset pulse matters, so it may be
+        // stretched by interrupts.
+        self.pull_low();
+        self.delay.delay_us(480);
+        let mut present = false;
+        (self.atomic)(&mut || {
+            self.release();
+            self.delay.delay_us(70);
+            present = !self.pin.read();
+        });
+        self.delay.delay_us(410);
+        present
+    }
+
+    pub fn write_bit(&self, bit: bool) {
+        (self.atomic)(&mut || {
+            self.pull_low();
+            if bit {
+                self.delay.delay_us(6);
+                self.release();
+                self.delay.delay_us(64);
+            } else {
+                self.delay.delay_us(60);
+                self.release();
+                self.delay.delay_us(10);
+            }
+        });
+    }
+
+    pub fn read_bit(&self) -> bool {
+        let mut bit = false;
+        (self.atomic)(&mut || {
+            self.pull_low();
+            self.delay.delay_us(6);
+            self.release();
+            self.delay.delay_us(9);
+            bit = self.pin.read();
+            self.delay.delay_us(55);
+        });
+        bit
+    }
+
+    /// Write a byte, least significant bit first.
+    pub fn write_byte(&self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+
+    /// Read a byte, least significant bit first.
+    pub fn read_byte(&self) -> u8 {
+        (0..8).fold(0, |byte, i| byte | (self.read_bit() as u8) << i)
+    }
+
+    /// Read the ROM code of the only device on the bus. Fails with
+    /// `ENODEVICE` if there is no device, and with `FAIL` if the CRC does not
+    /// match, as happens when several devices answer at once.
+    pub fn read_rom(&self, rom: &mut [u8; 8]) -> ReturnCode {
+        if !self.reset() {
+            return ReturnCode::ENODEVICE;
+        }
+        self.write_byte(READ_ROM);
+        for byte in rom.iter_mut() {
+            *byte = self.read_byte();
+        }
+        if crc8(&rom[..7]) == rom[7] {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        }
+    }
+
+    /// Reset the bus and address the device with ROM code `rom`. The next
+    /// command is only seen by that device.
+    pub fn select(&self, rom: &[u8; 8]) -> ReturnCode {
+        if !self.reset() {
+            return ReturnCode::ENODEVICE;
+        }
+        self.write_byte(MATCH_ROM);
+        for byte in rom.iter() {
+            self.write_byte(*byte);
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Reset the bus and address every device on it, for buses with a single
+    /// device or commands that all devices should run.
+    pub fn skip_rom(&self) -> ReturnCode {
+        if !self.reset() {
+            return ReturnCode::ENODEVICE;
+        }
+        self.write_byte(SKIP_ROM);
+        ReturnCode::SUCCESS
+    }
+}
+
+/// The Dallas/Maxim CRC-8 (polynomial x^8 + x^5 + x^4 + 1) used for 1-Wire
+/// ROM codes and device memory.
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+impl<D: BusyWait> Driver for OneWire<'a, D> {
+    fn command(&self, command_num: usize, arg1: usize, _: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                if self.reset() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::ENODEVICE
+                }
+            }
+            2 => {
+                self.write_byte(arg1 as u8);
+                ReturnCode::SUCCESS
+            }
+            3 => ReturnCode::SuccessWithValue {
+                value: self.read_byte() as usize,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Provides userspace with control over the power state of the system.
+//!
+//! The only operation is entering shutdown, the lowest power state, in which
+//! everything but the wake-up logic is powered off. This is meant for devices
+//! such as trackers that report very infrequently and should draw as little
+//! current as possible in between. Leaving shutdown resets the chip, so every
+//! process starts again from the beginning; apps should save any state they
+//! need before asking for shutdown.
+//!
+//! Because shutting down stops every app, not just the caller, the board has
+//! to hold the `PowerManagementCapability` to instantiate this driver, and
+//! passes a policy function that decides which processes may use it. All
+//! commands from other processes fail with ENOSUPPORT.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let power = static_init!(
+//!     capsules::power::PowerDriver<'static, cc26x2::aon::Aon>,
+//!     capsules::power::PowerDriver::new(
+//!         &cc26x2::aon::AON,
+//!         |app| app.idx() == 0,
+//!         &power_management_capability
+//!     )
+//! );
+//! ```
+
+use kernel::capabilities::PowerManagementCapability;
+use kernel::hil;
+use kernel::{AppId, Driver, ReturnCode};
+
+/// Syscall number
+pub const DRIVER_NUM: usize = 0x10001;
+
+pub struct PowerDriver<'a, S: hil::power::Shutdown + 'a> {
+    shutdown: &'a S,
+    /// Whether a process may use this driver.
+    allowed: fn(AppId) -> bool,
+}
+
+impl<S: hil::power::Shutdown> PowerDriver<'a, S> {
+    pub fn new(
+        shutdown: &'a S,
+        allowed: fn(AppId) -> bool,
+        _capability: &PowerManagementCapability,
+    ) -> PowerDriver<'a, S> {
+        PowerDriver {
+            shutdown: shutdown,
+            allowed: allowed,
+        }
+    }
+}
+
+impl<S: hil::power::Shutdown> Driver for PowerDriver<'a, S> {
+    /// Control the power state of the system. Every command fails with
+    /// ENOSUPPORT for processes that the board's policy does not allow.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Enter shutdown. Only returns on failure, with EBUSY if a wake-up
+    ///        source is already asserted, or FAIL if the chip did not shut
+    ///        down.
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        if !(self.allowed)(appid) {
+            return ReturnCode::ENOSUPPORT;
+        }
+
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.shutdown.shutdown(),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Encodes sensor readings as CBOR-serialized SenML records.
+//!
+//! Applications that report sensor data to a gateway usually need to produce
+//! a SenML (RFC 8428) payload. Rather than having each application link its
+//! own CBOR encoder, this capsule encodes an array of fixed-size reading
+//! records into a SenML pack in kernel space. The encoder itself is also
+//! exposed as `Encoder` so that other capsules can produce payloads without
+//! going through the system call interface.
+//!
+//! Only the subset of SenML needed for numeric sensor data is supported: an
+//! optional base name (`bn`), and per-record unit (`u`), value (`v`) and
+//! time (`t`) fields. Values with a non-zero decimal exponent are encoded as
+//! CBOR decimal fractions (tag 4).
+//!
+//! Reading Format
+//! --------------
+//!
+//! Each reading provided by an application is 12 bytes, little-endian:
+//!
+//! ```text
+//! 0      1      2             4                   8                   12
+//! +------+------+-------------+-------------------+-------------------+
+//! | unit | exp  |    flags    |   value (i32)     |    time (i32)     |
+//! +------+------+-------------+-------------------+-------------------+
+//! ```
+//!
+//! - `unit` selects one of the SenML units listed in `Unit`.
+//! - `exp` is a signed decimal exponent applied to `value`.
+//! - `flags` bit 0 indicates that `time` is valid; the time is encoded as a
+//!   relative time in seconds.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let senml = static_init!(
+//!     capsules::senml::SenmlDriver,
+//!     capsules::senml::SenmlDriver::new(board_kernel.create_grant(&grant_cap)));
+//! ```
+
+use enum_primitive::cast::FromPrimitive;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall number
+pub const DRIVER_NUM: usize = 0x30003;
+
+/// Size in bytes of a single reading record in the application buffer.
+pub const READING_LEN: usize = 12;
+
+/// Flag indicating that the `time` field of a reading is valid.
+pub const FLAG_HAS_TIME: u16 = 0x1;
+
+// SenML CBOR labels (RFC 8428, Section 6).
+const LABEL_BASE_NAME: i32 = -2;
+const LABEL_UNIT: i32 = 1;
+const LABEL_VALUE: i32 = 2;
+const LABEL_TIME: i32 = 6;
+
+// CBOR major types.
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+// CBOR tag for decimal fractions (RFC 7049, Section 2.4.3).
+const TAG_DECIMAL_FRACTION: u32 = 4;
+
+enum_from_primitive!{
+/// SenML units that a reading can be tagged with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    None = 0,
+    Celsius = 1,
+    RelativeHumidity = 2,
+    Volt = 3,
+    Ampere = 4,
+    Watt = 5,
+    Pascal = 6,
+    Lux = 7,
+    Meter = 8,
+    MeterPerSecondSquared = 9,
+    Percent = 10,
+    DecibelMilliwatt = 11,
+    Count = 12,
+}
+}
+
+impl Unit {
+    /// The SenML unit string for this unit, or `None` if the record should
+    /// carry no unit.
+    pub fn as_str(&self) -> Option<&'static str> {
+        match *self {
+            Unit::None => None,
+            Unit::Celsius => Some("Cel"),
+            Unit::RelativeHumidity => Some("%RH"),
+            Unit::Volt => Some("V"),
+            Unit::Ampere => Some("A"),
+            Unit::Watt => Some("W"),
+            Unit::Pascal => Some("Pa"),
+            Unit::Lux => Some("lx"),
+            Unit::Meter => Some("m"),
+            Unit::MeterPerSecondSquared => Some("m/s2"),
+            Unit::Percent => Some("%"),
+            Unit::DecibelMilliwatt => Some("dBm"),
+            Unit::Count => Some("count"),
+        }
+    }
+}
+
+/// A single sensor reading to be encoded as a SenML record.
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    pub unit: Unit,
+    pub exponent: i8,
+    pub value: i32,
+    pub time: Option<i32>,
+}
+
+impl Reading {
+    /// Decodes a reading from its 12 byte application representation.
+    /// Returns `None` if the record is too short or names an unknown unit.
+    pub fn decode(buf: &[u8]) -> Option<Reading> {
+        if buf.len() < READING_LEN {
+            return None;
+        }
+        let unit = Unit::from_u8(buf[0])?;
+        let flags = buf[2] as u16 | (buf[3] as u16) << 8;
+        let value = read_i32(&buf[4..8]);
+        let time = if flags & FLAG_HAS_TIME != 0 {
+            Some(read_i32(&buf[8..12]))
+        } else {
+            None
+        };
+        Some(Reading {
+            unit: unit,
+            exponent: buf[1] as i8,
+            value: value,
+            time: time,
+        })
+    }
+}
+
+fn read_i32(buf: &[u8]) -> i32 {
+    (buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24) as i32
+}
+
+/// Writes CBOR data items into a caller-provided buffer.
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Encoder<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Encoder<'a> {
+        Encoder { buf: buf, len: 0 }
+    }
+
+    /// Number of bytes encoded so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    fn put(&mut self, byte: u8) -> Result<(), ReturnCode> {
+        if self.len >= self.buf.len() {
+            return Err(ReturnCode::ESIZE);
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Writes an initial byte with the given major type and argument, using
+    /// the shortest encoding for the argument.
+    fn header(&mut self, major: u8, arg: u32) -> Result<(), ReturnCode> {
+        let major = major << 5;
+        if arg < 24 {
+            self.put(major | arg as u8)
+        } else if arg <= 0xff {
+            self.put(major | 24)?;
+            self.put(arg as u8)
+        } else if arg <= 0xffff {
+            self.put(major | 25)?;
+            self.put((arg >> 8) as u8)?;
+            self.put(arg as u8)
+        } else {
+            self.put(major | 26)?;
+            self.put((arg >> 24) as u8)?;
+            self.put((arg >> 16) as u8)?;
+            self.put((arg >> 8) as u8)?;
+            self.put(arg as u8)
+        }
+    }
+
+    pub fn int(&mut self, value: i32) -> Result<(), ReturnCode> {
+        if value < 0 {
+            // CBOR encodes a negative integer n as -1 - n.
+            self.header(MAJOR_NEGATIVE, !(value as u32))
+        } else {
+            self.header(MAJOR_UNSIGNED, value as u32)
+        }
+    }
+
+    pub fn text(&mut self, text: &[u8]) -> Result<(), ReturnCode> {
+        self.header(MAJOR_TEXT, text.len() as u32)?;
+        for &byte in text.iter() {
+            self.put(byte)?;
+        }
+        Ok(())
+    }
+
+    pub fn array(&mut self, items: usize) -> Result<(), ReturnCode> {
+        self.header(MAJOR_ARRAY, items as u32)
+    }
+
+    pub fn map(&mut self, pairs: usize) -> Result<(), ReturnCode> {
+        self.header(MAJOR_MAP, pairs as u32)
+    }
+
+    /// Encodes `mantissa * 10^exponent`, as a plain integer when the exponent
+    /// is zero and as a decimal fraction otherwise.
+    pub fn decimal(&mut self, mantissa: i32, exponent: i8) -> Result<(), ReturnCode> {
+        if exponent == 0 {
+            return self.int(mantissa);
+        }
+        self.header(MAJOR_TAG, TAG_DECIMAL_FRACTION)?;
+        self.array(2)?;
+        self.int(exponent as i32)?;
+        self.int(mantissa)
+    }
+
+    /// Encodes a single SenML record. A pack of `n` records is `array(n)`
+    /// followed by the records, with a non-empty `base_name` only on the
+    /// first one.
+    pub fn senml_record(&mut self, base_name: &[u8], reading: &Reading) -> Result<(), ReturnCode> {
+        let unit = reading.unit.as_str();
+        let pairs = 1
+            + if base_name.len() > 0 { 1 } else { 0 }
+            + if unit.is_some() { 1 } else { 0 }
+            + if reading.time.is_some() { 1 } else { 0 };
+        self.map(pairs)?;
+        if base_name.len() > 0 {
+            self.int(LABEL_BASE_NAME)?;
+            self.text(base_name)?;
+        }
+        if let Some(unit) = unit {
+            self.int(LABEL_UNIT)?;
+            self.text(unit.as_bytes())?;
+        }
+        self.int(LABEL_VALUE)?;
+        self.decimal(reading.value, reading.exponent)?;
+        if let Some(time) = reading.time {
+            self.int(LABEL_TIME)?;
+            self.int(time)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    readings: Option<AppSlice<Shared, u8>>,
+    output: Option<AppSlice<Shared, u8>>,
+    base_name: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct SenmlDriver {
+    apps: Grant<App>,
+}
+
+impl SenmlDriver {
+    pub fn new(grant: Grant<App>) -> SenmlDriver {
+        SenmlDriver { apps: grant }
+    }
+
+    fn encode(&self, app: &mut App, count: usize) -> ReturnCode {
+        let readings = match app.readings {
+            Some(ref readings) => readings,
+            None => return ReturnCode::EINVAL,
+        };
+        if count == 0
+            || count
+                .checked_mul(READING_LEN)
+                .map_or(true, |len| len > readings.len())
+        {
+            return ReturnCode::ESIZE;
+        }
+        let base_name = app.base_name.as_ref().map_or(&[][..], |name| name.as_ref());
+        let output = match app.output {
+            Some(ref mut output) => output,
+            None => return ReturnCode::EINVAL,
+        };
+
+        let mut encoder = Encoder::new(output.as_mut());
+        let result = encoder.array(count).and_then(|_| {
+            for (i, record) in readings.as_ref()[..count * READING_LEN]
+                .chunks(READING_LEN)
+                .enumerate()
+            {
+                let reading = Reading::decode(record).ok_or(ReturnCode::EINVAL)?;
+                encoder.senml_record(if i == 0 { base_name } else { &[] }, &reading)?;
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => ReturnCode::SuccessWithValue {
+                value: encoder.len(),
+            },
+            Err(err) => err,
+        }
+    }
+}
+
+impl Driver for SenmlDriver {
+    /// Setup buffers for encoding.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Buffer of 12 byte reading records to encode.
+    /// - `1`: Buffer the CBOR-encoded SenML pack is written to.
+    /// - `2`: Optional base name attached to the first record.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| match allow_num {
+                0 => {
+                    app.readings = slice;
+                    ReturnCode::SUCCESS
+                }
+                1 => {
+                    app.output = slice;
+                    ReturnCode::SUCCESS
+                }
+                2 => {
+                    app.base_name = slice;
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::ENOSUPPORT,
+            }).unwrap_or_else(|err| err.into())
+    }
+
+    /// Encode readings.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Encode the first `data` readings from the readings buffer into
+    ///        the output buffer. Encoding is synchronous; on success the
+    ///        number of bytes written is returned. Returns `ESIZE` if the
+    ///        output buffer is too small or the readings buffer holds fewer
+    ///        than `data` records, and `EINVAL` if a buffer is missing or a
+    ///        record names an unknown unit.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+
+            1 => self
+                .apps
+                .enter(appid, |app, _| self.encode(app, data))
+                .unwrap_or_else(|err| err.into()),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Software I2C master that bit-bangs two GPIO pins.
+//!
+//! This is for devices on pins that no I2C controller can reach. Both lines
+//! are driven open-drain: a line is pulled low by making its pin an output at
+//! zero, and released by making it an input, so both need external pull-up
+//! resistors. The bus runs at up to 100kHz (standard mode) and allows
+//! devices to stretch the clock.
+//!
+//! A transfer busy-waits for its whole duration, about 100us per byte. It does
+//! not run inside the call that requests it, but from an alarm that fires
+//! shortly after, so clients such as `MuxI2C` see completion asynchronously,
+//! as they would with a hardware controller.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let delay = static_init!(
+//!     cortexm4::dwt::CycleDelay,
+//!     cortexm4::dwt::CycleDelay::new(48_000_000)
+//! );
+//! let soft_i2c_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let soft_i2c = static_init!(
+//!     capsules::soft_i2c::SoftI2C<
+//!         'static,
+//!         VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!         cortexm4::dwt::CycleDelay,
+//!     >,
+//!     capsules::soft_i2c::SoftI2C::new(
+//!         &cc26x2::gpio::PORT[22],
+//!         &cc26x2::gpio::PORT[23],
+//!         soft_i2c_alarm,
+//!         delay
+//!     )
+//! );
+//! soft_i2c_alarm.set_client(soft_i2c);
+//! let i2c_mux = static_init!(MuxI2C<'static>, MuxI2C::new(soft_i2c));
+//! soft_i2c.set_client(i2c_mux);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::gpio;
+use kernel::hil::i2c::{self, Error};
+use kernel::hil::time::{self, BusyWait, Frequency};
+
+/// Half of a 100kHz clock period.
+const HALF_PERIOD_US: u32 = 5;
+
+/// How long a device may hold the clock low before the transfer is abandoned.
+const CLOCK_STRETCH_TIMEOUT_US: u32 = 10_000;
+
+/// Delay between a request and the start of its transfer.
+const START_DELAY_US: u32 = 100;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Op {
+    Idle,
+    Write(u8),
+    Read(u8),
+    WriteRead(u8, u8),
+}
+
+pub struct SoftI2C<'a, A: time::Alarm + 'a, D: BusyWait + 'a> {
+    scl: &'a gpio::Pin,
+    sda: &'a gpio::Pin,
+    alarm: &'a A,
+    delay: &'a D,
+    addr: Cell<u8>,
+    op: Cell<Op>,
+    buffer: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'a i2c::I2CHwMasterClient>,
+}
+
+impl<A: time::Alarm, D: BusyWait> SoftI2C<'a, A, D> {
+    pub fn new(
+        scl: &'a gpio::Pin,
+        sda: &'a gpio::Pin,
+        alarm: &'a A,
+        delay: &'a D,
+    ) -> SoftI2C<'a, A, D> {
+        SoftI2C {
+            scl: scl,
+            sda: sda,
+            alarm: alarm,
+            delay: delay,
+            addr: Cell::new(0),
+            op: Cell::new(Op::Idle),
+            buffer: TakeCell::empty(),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a i2c::I2CHwMasterClient) {
+        self.client.set(client);
+    }
+
+    fn pull_low(&self, pin: &gpio::Pin) {
+        pin.clear();
+        pin.make_output();
+    }
+
+    fn wait(&self) {
+        self.delay.delay_us(HALF_PERIOD_US);
+    }
+
+    /// Release the clock and wait for any device stretching it to let go.
+    fn release_scl(&self) -> Result<(), Error> {
+        self.scl.make_input();
+        let mut waited = 0;
+        while !self.scl.read() {
+            if waited == CLOCK_STRETCH_TIMEOUT_US {
+                return Err(Error::ArbitrationLost);
+            }
+            self.delay.delay_us(1);
+            waited += 1;
+        }
+        self.wait();
+        Ok(())
+    }
+
+    /// Send a start, or a repeated start if the bus is already held.
+    fn start(&self) -> Result<(), Error> {
+        self.sda.make_input();
+        self.wait();
+        self.release_scl()?;
+        if !self.sda.read() {
+            // Another master or a stuck device is holding the data line
+            return Err(Error::ArbitrationLost);
+        }
+        self.pull_low(self.sda);
+        self.wait();
+        self.pull_low(self.scl);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        self.pull_low(self.sda);
+        self.wait();
+        let _ = self.release_scl();
+        self.sda.make_input();
+        self.wait();
+    }
+
+    /// Clock out one bit, or clock one in with the data line released.
+    fn clock_bit(&self, bit: bool) -> Result<bool, Error> {
+        if bit {
+            self.sda.make_input();
+        } else {
+            self.pull_low(self.sda);
+        }
+        self.wait();
+        self.release_scl()?;
+        let value = self.sda.read();
+        self.pull_low(self.scl);
+        Ok(value)
+    }
+
+    /// Write a byte, returning whether the device acknowledged it.
+    fn write_byte(&self, byte: u8) -> Result<bool, Error> {
+        for i in (0..8).rev() {
+            self.clock_bit(byte & (1 << i) != 0)?;
+        }
+        Ok(!self.clock_bit(true)?)
+    }
+
+    /// Read a byte, acknowledging it if more are to follow.
+    fn read_byte(&self, ack: bool) -> Result<u8, Error> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = byte << 1 | self.clock_bit(true)? as u8;
+        }
+        self.clock_bit(!ack)?;
+        Ok(byte)
+    }
+
+    fn address(&self, read: bool) -> Result<(), Error> {
+        if self.write_byte(self.addr.get() << 1 | read as u8)? {
+            Ok(())
+        } else {
+            Err(Error::AddressNak)
+        }
+    }
+
+    fn transfer(&self, buffer: &mut [u8], write_len: usize, read_len: usize) -> Result<(), Error> {
+        if write_len > 0 {
+            self.start()?;
+            self.address(false)?;
+            for byte in buffer[..write_len].iter() {
+                if !self.write_byte(*byte)? {
+                    return Err(Error::DataNak);
+                }
+            }
+        }
+        if read_len > 0 {
+            self.start()?;
+            self.address(true)?;
+            for i in 0..read_len {
+                buffer[i] = self.read_byte(i + 1 < read_len)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, addr: u8, buffer: &'static mut [u8], op: Op) {
+        self.addr.set(addr);
+        self.buffer.replace(buffer);
+        self.op.set(op);
+        let delay = (START_DELAY_US as u64 * <A::Frequency>::frequency() as u64 / 1_000_000) as u32;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(cmp::max(delay, 1)));
+    }
+}
+
+impl<A: time::Alarm, D: BusyWait> time::Client for SoftI2C<'a, A, D> {
+    fn fired(&self) {
+        let op = self.op.get();
+        self.op.set(Op::Idle);
+        self.buffer.take().map(|buffer| {
+            let (write_len, read_len) = match op {
+                Op::Idle => (0, 0),
+                Op::Write(len) => (len as usize, 0),
+                Op::Read(len) => (0, len as usize),
+                Op::WriteRead(write_len, read_len) => (write_len as usize, read_len as usize),
+            };
+            let write_len = cmp::min(write_len, buffer.len());
+            let read_len = cmp::min(read_len, buffer.len());
+
+            let result = self.transfer(buffer, write_len, read_len);
+            self.stop();
+            let error = result.err().unwrap_or(Error::CommandComplete);
+            self.client
+                .map(move |client| client.command_complete(buffer, error));
+        });
+    }
+}
+
+impl<A: time::Alarm, D: BusyWait> i2c::I2CMaster for SoftI2C<'a, A, D> {
+    /// Release both lines. The pins stay inputs whenever no transfer is
+    /// running, so there is nothing else to enable or disable.
+    fn enable(&self) {
+        self.scl.make_input();
+        self.sda.make_input();
+    }
+
+    fn disable(&self) {}
+
+    fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        self.request(addr, data, Op::WriteRead(write_len, read_len));
+    }
+
+    fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
+        self.request(addr, data, Op::Write(len));
+    }
+
+    fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
+        self.request(addr, buffer, Op::Read(len));
+    }
+}
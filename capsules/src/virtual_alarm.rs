@@ -1,5 +1,22 @@
 //! Virtualize the Alarm interface to enable multiple users of an underlying
 //! alarm hardware peripheral.
+//!
+//! Two multiplexers are provided. `MuxAlarm` keeps its `VirtualMuxAlarm`s in
+//! a list, which is cheap for a handful of users. `WheelMuxAlarm` files its
+//! `VirtualWheelAlarm`s into a timer wheel and is better suited to boards
+//! with many concurrently armed alarms. Both are set up the same way:
+//!
+//! ```rust
+//! let mux_alarm = static_init!(
+//!     capsules::virtual_alarm::WheelMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!     capsules::virtual_alarm::WheelMuxAlarm::new(&cc26x2::rtc::RTC)
+//! );
+//! rtc.set_client(mux_alarm);
+//! let virtual_alarm = static_init!(
+//!     capsules::virtual_alarm::VirtualWheelAlarm<'static, cc26x2::rtc::Rtc>,
+//!     capsules::virtual_alarm::VirtualWheelAlarm::new(mux_alarm)
+//! );
+//! ```
 
 use core::cell::Cell;
 use kernel::common::cells::OptionalCell;
@@ -170,3 +187,360 @@ impl<Alrm: Alarm> time::Client for MuxAlarm<'a, Alrm> {
         }
     }
 }
+
+// Timer wheel
+//
+// `MuxAlarm` keeps its virtual alarms in a list that has to be scanned in
+// full every time the underlying alarm fires. With many active alarms this
+// adds jitter to every expiry. `WheelMuxAlarm` instead files each armed alarm
+// into a hierarchical timer wheel, so arming and disarming are constant time
+// and only the alarms in the earliest occupied slot are looked at to find the
+// next deadline.
+//
+// Alarm times are split into `WHEEL_LEVELS` digits of `WHEEL_SLOT_BITS`
+// bits. An alarm is filed at the level of the most significant digit in
+// which it differs from the wheel's `base` time, in the slot given by that
+// digit. Every alarm at a lower level therefore expires before any alarm at
+// a higher level, and within a level slots expire in order. When the start of
+// a slot at a higher level is reached, its alarms cascade down to lower
+// levels. The top level wraps around to handle the 32 bit counter wrapping.
+
+/// Number of bits of the alarm time resolved by each level of the wheel.
+const WHEEL_SLOT_BITS: u32 = 4;
+const WHEEL_SLOTS: usize = 1 << WHEEL_SLOT_BITS;
+/// Enough levels to cover the full 32 bit alarm range.
+const WHEEL_LEVELS: usize = 32 / WHEEL_SLOT_BITS as usize;
+
+/// Maximum number of virtual alarms that can share one `WheelMuxAlarm`.
+pub const WHEEL_MAX_ALARMS: usize = 32;
+
+/// Iterator over the indices of the set bits of a slot bitmap.
+struct Bits(u32);
+
+impl Iterator for Bits {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            let index = self.0.trailing_zeros();
+            self.0 &= !(1 << index);
+            Some(index as usize)
+        }
+    }
+}
+
+pub struct VirtualWheelAlarm<'a, Alrm: Alarm> {
+    mux: &'a WheelMuxAlarm<'a, Alrm>,
+    /// Whether the alarm has been added to its mux.
+    registered: Cell<bool>,
+    index: Cell<usize>,
+    when: Cell<u32>,
+    armed: Cell<bool>,
+    /// The (level, slot) the alarm is filed under, or `None` if it has
+    /// already expired and is waiting to be fired.
+    position: Cell<Option<(usize, usize)>>,
+    client: OptionalCell<&'a time::Client>,
+}
+
+impl<Alrm: Alarm> VirtualWheelAlarm<'a, Alrm> {
+    pub fn new(mux_alarm: &'a WheelMuxAlarm<'a, Alrm>) -> VirtualWheelAlarm<'a, Alrm> {
+        VirtualWheelAlarm {
+            mux: mux_alarm,
+            registered: Cell::new(false),
+            index: Cell::new(0),
+            when: Cell::new(0),
+            armed: Cell::new(false),
+            position: Cell::new(None),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Registers this alarm with its mux the first time it is called.
+    /// Panics if the mux already has `WHEEL_MAX_ALARMS` alarms. Calling it
+    /// again only replaces the client and disarms the alarm.
+    pub fn set_client(&'a self, client: &'a time::Client) {
+        if self.registered.get() {
+            self.disable();
+        } else {
+            self.mux.register(self);
+            self.registered.set(true);
+        }
+        self.when.set(0);
+        self.armed.set(false);
+        self.client.set(client);
+    }
+}
+
+impl<Alrm: Alarm> Time for VirtualWheelAlarm<'a, Alrm> {
+    type Frequency = Alrm::Frequency;
+
+    fn disable(&self) {
+        if !self.armed.get() {
+            return;
+        }
+
+        self.armed.set(false);
+        self.mux.remove(self);
+
+        // If there are no more armed alarms, disable the underlying alarm
+        // completely.
+        if self.mux.is_empty() {
+            self.mux.alarm.disable();
+        }
+    }
+
+    fn is_armed(&self) -> bool {
+        self.armed.get()
+    }
+}
+
+impl<Alrm: Alarm> Alarm for VirtualWheelAlarm<'a, Alrm> {
+    fn now(&self) -> u32 {
+        self.mux.alarm.now()
+    }
+
+    fn set_alarm(&self, when: u32) {
+        if self.armed.get() {
+            self.mux.remove(self);
+        }
+
+        self.when.set(when);
+        self.armed.set(true);
+        let now = self.now();
+        // Bring the wheel up to date first. After the wheel has been idle,
+        // or its earliest alarm is overdue, its base time can be so far
+        // behind that `when` appears to have already passed.
+        self.mux.advance(now);
+        self.mux.insert(self, now);
+
+        // Only reprogram the underlying alarm if this alarm is due before the
+        // one it is currently set for. If the earlier alarm was disarmed in
+        // the meantime, the underlying alarm fires early and is reprogrammed
+        // then.
+        let cur_alarm = self.mux.alarm.get_alarm();
+        if !self.mux.alarm.is_armed() || cur_alarm.wrapping_sub(now) > when.wrapping_sub(now) {
+            self.mux.program();
+        }
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.when.get()
+    }
+}
+
+impl<Alrm: Alarm> time::Client for VirtualWheelAlarm<'a, Alrm> {
+    fn fired(&self) {
+        self.client.map(|client| client.fired());
+    }
+}
+
+// WheelMuxAlarm
+
+pub struct WheelMuxAlarm<'a, Alrm: Alarm> {
+    alarms: [Cell<Option<&'a VirtualWheelAlarm<'a, Alrm>>>; WHEEL_MAX_ALARMS],
+    registered: Cell<usize>,
+    /// Bitmap of the alarm indices filed in each slot.
+    slots: [[Cell<u32>; WHEEL_SLOTS]; WHEEL_LEVELS],
+    /// Bitmap of the non-empty slots in each level.
+    occupied: [Cell<u16>; WHEEL_LEVELS],
+    /// Bitmap of alarms that have expired but not yet been fired.
+    expired: Cell<u32>,
+    /// Time the wheel was last advanced to.
+    base: Cell<u32>,
+    alarm: &'a Alrm,
+}
+
+impl<Alrm: Alarm> WheelMuxAlarm<'a, Alrm> {
+    pub fn new(alarm: &'a Alrm) -> WheelMuxAlarm<'a, Alrm> {
+        WheelMuxAlarm {
+            alarms: Default::default(),
+            registered: Cell::new(0),
+            slots: Default::default(),
+            occupied: Default::default(),
+            expired: Cell::new(0),
+            base: Cell::new(alarm.now()),
+            alarm: alarm,
+        }
+    }
+
+    fn register(&self, valarm: &'a VirtualWheelAlarm<'a, Alrm>) {
+        let index = self.registered.get();
+        if index >= WHEEL_MAX_ALARMS {
+            panic!("WheelMuxAlarm: too many virtual alarms");
+        }
+        self.alarms[index].set(Some(valarm));
+        valarm.index.set(index);
+        self.registered.set(index + 1);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.expired.get() == 0 && self.occupied.iter().all(|level| level.get() == 0)
+    }
+
+    fn digit(time: u32, level: usize) -> usize {
+        (time >> (level as u32 * WHEEL_SLOT_BITS)) as usize & (WHEEL_SLOTS - 1)
+    }
+
+    /// File `valarm` into the wheel, or into the expired set if its time
+    /// has already passed at `now`.
+    fn insert(&self, valarm: &VirtualWheelAlarm<'a, Alrm>, now: u32) {
+        let bit = 1 << valarm.index.get();
+        let when = valarm.when.get();
+        let base = self.base.get();
+
+        if has_expired(when, now, base) {
+            self.expired.set(self.expired.get() | bit);
+            valarm.position.set(None);
+            return;
+        }
+
+        let level = ((31 - (when ^ base).leading_zeros()) / WHEEL_SLOT_BITS) as usize;
+        let slot = Self::digit(when, level);
+        if level < WHEEL_LEVELS - 1 && slot < Self::digit(base, level) {
+            // Only possible if `when` is just behind the wheel's base time,
+            // i.e. in the past.
+            self.expired.set(self.expired.get() | bit);
+            valarm.position.set(None);
+            return;
+        }
+
+        self.slots[level][slot].set(self.slots[level][slot].get() | bit);
+        self.occupied[level].set(self.occupied[level].get() | 1 << slot);
+        valarm.position.set(Some((level, slot)));
+    }
+
+    fn remove(&self, valarm: &VirtualWheelAlarm<'a, Alrm>) {
+        let bit = 1 << valarm.index.get();
+        match valarm.position.get() {
+            Some((level, slot)) => {
+                let remaining = self.slots[level][slot].get() & !bit;
+                self.slots[level][slot].set(remaining);
+                if remaining == 0 {
+                    self.occupied[level].set(self.occupied[level].get() & !(1 << slot));
+                }
+            }
+            None => self.expired.set(self.expired.get() & !bit),
+        }
+    }
+
+    /// The earliest non-empty slot in the wheel.
+    fn first_slot(&self) -> Option<(usize, usize)> {
+        for level in 0..WHEEL_LEVELS {
+            let occupied = self.occupied[level].get();
+            if occupied == 0 {
+                continue;
+            }
+            let slot = if level == WHEEL_LEVELS - 1 {
+                // The top level wraps around, so search from the slot after
+                // the base time's.
+                let start = (Self::digit(self.base.get(), level) + 1) % WHEEL_SLOTS;
+                (start + occupied.rotate_right(start as u32).trailing_zeros() as usize)
+                    % WHEEL_SLOTS
+            } else {
+                occupied.trailing_zeros() as usize
+            };
+            return Some((level, slot));
+        }
+        None
+    }
+
+    /// The time at which `slot` of `level` begins.
+    fn slot_start(&self, level: usize, slot: usize) -> u32 {
+        let shift = level as u32 * WHEEL_SLOT_BITS;
+        let upper = if level == WHEEL_LEVELS - 1 {
+            0
+        } else {
+            self.base.get() & !((1 << (shift + WHEEL_SLOT_BITS)) - 1)
+        };
+        upper | (slot as u32) << shift
+    }
+
+    /// Set the underlying alarm for the next expiry. Returns the time it was
+    /// set to, if any.
+    fn program(&self) -> Option<u32> {
+        if self.expired.get() != 0 {
+            let next = self.alarm.now().wrapping_add(1);
+            self.alarm.set_alarm(next);
+            return Some(next);
+        }
+
+        match self.first_slot() {
+            Some((level, slot)) => {
+                // Every alarm in the earliest occupied slot is due before any
+                // alarm in another slot, so only this slot needs to be
+                // searched.
+                let base = self.base.get();
+                let next = Bits(self.slots[level][slot].get())
+                    .filter_map(|index| self.alarms[index].get())
+                    .map(|valarm| valarm.when.get())
+                    .min_by_key(|when| when.wrapping_sub(base));
+                next.map(|when| self.alarm.set_alarm(when));
+                next
+            }
+            None => {
+                self.alarm.disable();
+                None
+            }
+        }
+    }
+
+    /// Advance the wheel to `now`, cascading slots whose start time has been
+    /// reached and moving due alarms into the expired set.
+    fn advance(&self, now: u32) {
+        while let Some((level, slot)) = self.first_slot() {
+            let start = self.slot_start(level, slot);
+            if !has_expired(start, now, self.base.get()) {
+                break;
+            }
+
+            let alarms = self.slots[level][slot].get();
+            self.slots[level][slot].set(0);
+            self.occupied[level].set(self.occupied[level].get() & !(1 << slot));
+            self.base.set(start);
+            for index in Bits(alarms) {
+                self.alarms[index]
+                    .get()
+                    .map(|valarm| self.insert(valarm, now));
+            }
+        }
+        self.base.set(now);
+    }
+}
+
+impl<Alrm: Alarm> time::Client for WheelMuxAlarm<'a, Alrm> {
+    fn fired(&self) {
+        loop {
+            self.advance(self.alarm.now());
+
+            // Fire expired alarms. At this level, alarms are one-shot, so a
+            // repeating client will set it again in the fired() callback.
+            let expired = self.expired.get();
+            self.expired.set(0);
+            for index in Bits(expired) {
+                self.alarms[index].get().map(|valarm| {
+                    // Skip alarms an earlier callback has re-armed.
+                    if valarm.armed.get()
+                        && valarm.position.get().is_none()
+                        && self.expired.get() & (1 << index) == 0
+                    {
+                        valarm.armed.set(false);
+                        valarm.fired();
+                    }
+                });
+            }
+
+            // If the next alarm became due while setting it, go around again
+            // rather than waiting for the counter to wrap.
+            let base = self.base.get();
+            let missed = match self.program() {
+                Some(next) => self.expired.get() == 0 && has_expired(next, self.alarm.now(), base),
+                None => false,
+            };
+            if !missed {
+                break;
+            }
+        }
+    }
+}
@@ -10,6 +10,14 @@
 //! `UartMux` provides shared access to a single UART bus for multiple users.
 //! `UartDevice` provides access for a single client.
 //!
+//! A device can be marked as high priority with `set_priority()`, which is
+//! intended for kernel debug output. Pending transmissions from high priority
+//! devices are always started before those of other devices. If the mux is
+//! also given a chunk buffer with `set_chunk_buffer()`, long transmissions
+//! from other devices are sent through it one chunk at a time, so a high
+//! priority transmission waits for at most one chunk before it starts, and
+//! the interrupted transmission resumes where it left off afterwards.
+//!
 //! Usage
 //! -----
 //!
@@ -35,6 +43,12 @@
 //!     )
 //! );
 //! hil::uart::UART::set_client(console_uart, console);
+//!
+//! // Let kernel debug output preempt long console writes.
+//! uart_mux.set_chunk_buffer(&mut capsules::virtual_uart::TX_CHUNK_BUF);
+//! let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+//! debugger_uart.setup();
+//! debugger_uart.set_priority(true);
 //! ```
 
 use core::cell::Cell;
@@ -49,6 +63,11 @@ use kernel::ReturnCode;
 const RX_BUF_LEN: usize = 64;
 pub static mut RX_BUF: [u8; RX_BUF_LEN] = [0; RX_BUF_LEN];
 
+/// At 115200 baud a chunk of this size takes under 3 ms to send, which bounds
+/// how long a high priority device waits behind another device's write.
+const TX_CHUNK_LEN: usize = 32;
+pub static mut TX_CHUNK_BUF: [u8; TX_CHUNK_LEN] = [0; TX_CHUNK_LEN];
+
 pub struct UartMux<'a> {
     uart: &'a hil::uart::UART,
     speed: u32,
@@ -56,13 +75,33 @@ pub struct UartMux<'a> {
     inflight: OptionalCell<&'a UartDevice<'a>>,
     buffer: TakeCell<'static, [u8]>,
     completing_read: Cell<bool>,
+    chunk_buffer: TakeCell<'static, [u8]>,
+    /// Whether the in flight transmission is a chunk sent from `chunk_buffer`
+    /// rather than the device's own buffer.
+    inflight_chunk: Cell<bool>,
 }
 
 impl<'a> hil::uart::Client for UartMux<'a> {
     fn transmit_complete(&self, tx_buffer: &'static mut [u8], error: hil::uart::Error) {
         self.inflight.map(move |device| {
             self.inflight.clear();
-            device.transmit_complete(tx_buffer, error);
+            if self.inflight_chunk.get() {
+                self.inflight_chunk.set(false);
+                self.chunk_buffer.replace(tx_buffer);
+                // Leave the rest of the transmission pending, so that a high
+                // priority device can go first, unless it is done or failed.
+                let done = device.operation.map_or(true, |op| match *op {
+                    Operation::Transmit { len } => device.tx_position.get() >= len,
+                });
+                if done || error != hil::uart::Error::CommandComplete {
+                    device.operation.clear();
+                    device.tx_buffer.take().map(|buf| {
+                        device.transmit_complete(buf, error);
+                    });
+                }
+            } else {
+                device.transmit_complete(tx_buffer, error);
+            }
         });
         self.do_next_op();
     }
@@ -149,9 +188,17 @@ impl<'a> UartMux<'a> {
             inflight: OptionalCell::empty(),
             buffer: TakeCell::new(buffer),
             completing_read: Cell::new(false),
+            chunk_buffer: TakeCell::empty(),
+            inflight_chunk: Cell::new(false),
         }
     }
 
+    /// Give the mux a buffer through which transmissions from devices that
+    /// are not high priority are split into chunks of at most its length.
+    pub fn set_chunk_buffer(&self, buffer: &'static mut [u8]) {
+        self.chunk_buffer.replace(buffer);
+    }
+
     pub fn initialize(&self) {
         self.uart.configure(uart::UARTParameters {
             baud_rate: self.speed,
@@ -163,14 +210,42 @@ impl<'a> UartMux<'a> {
 
     fn do_next_op(&self) {
         if self.inflight.is_none() {
-            let mnode = self.devices.iter().find(|node| node.operation.is_some());
+            let mnode = self
+                .devices
+                .iter()
+                .find(|node| node.priority.get() && node.operation.is_some())
+                .or_else(|| {
+                    // Finish a partly sent transmission before starting
+                    // another device's, so writes are not interleaved.
+                    self.devices
+                        .iter()
+                        .find(|node| node.operation.is_some() && node.tx_position.get() > 0)
+                }).or_else(|| self.devices.iter().find(|node| node.operation.is_some()));
             mnode.map(|node| {
-                node.tx_buffer.take().map(|buf| {
-                    node.operation.map(move |op| match op {
-                        Operation::Transmit { len } => self.uart.transmit(buf, *len),
-                    });
+                let len = node.operation.map_or(0, |op| match *op {
+                    Operation::Transmit { len } => len,
                 });
-                node.operation.clear();
+                let position = node.tx_position.get();
+                let chunk_len = self.chunk_buffer.map_or(0, |chunk| chunk.len());
+                if node.priority.get() || chunk_len == 0 || (position == 0 && len <= chunk_len) {
+                    // Send straight from the device's buffer.
+                    node.tx_buffer
+                        .take()
+                        .map(|buf| self.uart.transmit(buf, len));
+                    node.operation.clear();
+                } else {
+                    // Copy the next chunk out of the device's buffer and send
+                    // that, leaving the operation pending until the last one.
+                    let count = cmp::min(len - position, chunk_len);
+                    self.chunk_buffer.take().map(|chunk| {
+                        node.tx_buffer.map(|buf| {
+                            chunk[..count].copy_from_slice(&buf[position..position + count]);
+                        });
+                        node.tx_position.set(position + count);
+                        self.inflight_chunk.set(true);
+                        self.uart.transmit(chunk, count);
+                    });
+                }
                 self.inflight.set(node);
             });
         }
@@ -223,7 +298,9 @@ pub struct UartDevice<'a> {
     state: Cell<UartDeviceReceiveState>,
     mux: &'a UartMux<'a>,
     receiver: bool, // Whether or not to pass this UartDevice incoming messages.
+    priority: Cell<bool>,
     tx_buffer: TakeCell<'static, [u8]>,
+    tx_position: Cell<usize>,
     rx_buffer: TakeCell<'static, [u8]>,
     rx_position: Cell<usize>,
     rx_len: Cell<usize>,
@@ -238,7 +315,9 @@ impl<'a> UartDevice<'a> {
             state: Cell::new(UartDeviceReceiveState::Idle),
             mux: mux,
             receiver: receiver,
+            priority: Cell::new(false),
             tx_buffer: TakeCell::empty(),
+            tx_position: Cell::new(0),
             rx_buffer: TakeCell::empty(),
             rx_position: Cell::new(0),
             rx_len: Cell::new(0),
@@ -252,6 +331,12 @@ impl<'a> UartDevice<'a> {
     pub fn setup(&'a self) {
         self.mux.devices.push_head(self);
     }
+
+    /// Mark this device as high priority, so that its transmissions are
+    /// started ahead of, and are never split like, those of other devices.
+    pub fn set_priority(&self, priority: bool) {
+        self.priority.set(priority);
+    }
 }
 
 impl<'a> hil::uart::Client for UartDevice<'a> {
@@ -293,7 +378,9 @@ impl<'a> hil::uart::UART for UartDevice<'a> {
 
     /// Transmit data.
     fn transmit(&self, tx_data: &'static mut [u8], tx_len: usize) {
+        let tx_len = cmp::min(tx_len, tx_data.len());
         self.tx_buffer.replace(tx_data);
+        self.tx_position.set(0);
         self.operation.set(Operation::Transmit { len: tx_len });
         self.mux.do_next_op();
     }
@@ -2,10 +2,15 @@
 //!
 //! AON is a set of peripherals which is _always on_ (eg. the RTC, MCU, etc).
 //!
-//! The current configuration disables all wake-up selectors, since the
-//! MCU never go to sleep and is always active.
+//! `setup()` applies defaults that only wake the MCU on RTC channel 1. Boards
+//! choose which AON events wake the MCU from standby with
+//! `set_wakeup_sources()`, and a power manager can adjust that set at runtime
+//! with `add_wakeup_source()` and `remove_wakeup_source()`.
+use gpio;
 use kernel::common::registers::{ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
 use rtc;
 
 #[repr(C)]
@@ -16,20 +21,27 @@ pub struct AonIocRegisters {
 
 #[repr(C)]
 pub struct AonEventRegisters {
-    mcu_wu_sel: ReadWrite<u32>,       // MCU Wake-up selector
-    aux_wu_sel: ReadWrite<u32>,       // AUX Wake-up selector
+    mcu_wu_sel: ReadWrite<u32>,       // MCU Wake-up selector, WU0-WU3
+    mcu_wu_sel1: ReadWrite<u32>,      // MCU Wake-up selector, WU4-WU7
     event_to_mcu_sel: ReadWrite<u32>, // Event selector for MCU Events
     rtc_sel: ReadWrite<u32>,          // RTC Capture event selector for AON_RTC
 }
 
+// Offsets from hw_aon_pmctl.h. The block starts with a reserved word, so
+// AUXSCECLK is at 0x04, not 0x00.
 #[repr(C)]
 struct AonPmCtlRegisters {
-    aux_clk: ReadWrite<u32, AuxClk::Register>,
-    ram_cfg: ReadWrite<u32, RamCfg::Register>,
-    pwr_ctl: ReadWrite<u32, PwrCtl::Register>,
-    pwr_stat: ReadOnly<u32, PwrStat::Register>,
-    shutdown: ReadWrite<u32, Shutdown::Register>,
-    _recharge: [u32; 4],
+    _reserved0: u32,                               // 0x00
+    aux_clk: ReadWrite<u32, AuxClk::Register>,     // 0x04 AUXSCECLK
+    ram_cfg: ReadWrite<u32, RamCfg::Register>,     // 0x08 RAMCFG
+    _reserved1: u32,                               // 0x0C
+    pwr_ctl: ReadWrite<u32, PwrCtl::Register>,     // 0x10 PWRCTL
+    pwr_stat: ReadOnly<u32, PwrStat::Register>,    // 0x14 PWRSTAT
+    shutdown: ReadWrite<u32, Shutdown::Register>,  // 0x18 SHUTDOWN
+    _recharge: [u32; 2],                           // 0x1C RECHARGECFG, RECHARGESTAT
+    _osc_cfg: u32,                                 // 0x24 OSCCFG
+    reset_ctl: ReadWrite<u32, ResetCtl::Register>, // 0x28 RESETCTL
+    sleep_ctl: ReadWrite<u32, SleepCtl::Register>, // 0x2C SLEEPCTL
 }
 
 register_bitfields![
@@ -73,9 +85,19 @@ register_bitfields![
         AUX_RESET_DONE OFFSET(0) NUMBITS(1) []
     ],
     Shutdown [
-        // Controls whether MCU & AUX requesting to be powered off
-        // will enable a transition to powerdown (0 = Enabled, 1 = Disabled)
-        PWR_DWN_DIS     OFFSET(0) NUMBITS(1) []
+        // Writing 1 enters shutdown, once the MCU and AUX have requested to
+        // be powered off
+        EN      OFFSET(0) NUMBITS(1) []
+    ],
+    ResetCtl [
+        // The last reset was a wake-up from shutdown
+        WU_FROM_SD      OFFSET(15) NUMBITS(1) [],
+        // The last reset was a wake-up from shutdown caused by an IO
+        GPIO_WU_FROM_SD OFFSET(14) NUMBITS(1) []
+    ],
+    SleepCtl [
+        // 0 = IO pad state is latched (frozen), 1 = IO pads follow the IOC
+        IO_PAD_SLEEP_DIS OFFSET(0) NUMBITS(1) []
     ],
     IocClk [
         EN  OFFSET(0) NUMBITS(1) []
@@ -83,8 +105,37 @@ register_bitfields![
 
 ];
 
+/// The number of AON events that can be selected to wake the MCU at once.
+pub const NUM_WAKEUP_SOURCES: usize = 8;
+
+/// Selector value for a wake-up slot that is not in use.
+const WAKEUP_NONE: u32 = 0x3F;
+
+/// AON events that can wake the MCU from standby.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u32)]
+pub enum WakeupSource {
+    /// An edge on any IO configured with edge detection and interrupts.
+    IoEdge = 0x20,
+    RtcCh0 = 0x23,
+    RtcCh1 = 0x24,
+    RtcCh2 = 0x25,
+    RtcCh0Delayed = 0x26,
+    RtcCh1Delayed = 0x27,
+    RtcCh2Delayed = 0x28,
+    RtcCombinedDelayed = 0x29,
+    RtcUpdate = 0x2A,
+    BatmonTemperature = 0x35,
+    BatmonVoltage = 0x36,
+}
+
 const AON_EVENT_BASE: StaticRef<AonEventRegisters> =
     unsafe { StaticRef::new(0x4009_3000 as *const AonEventRegisters) };
+
+/// AON cycles (about 30us each) to wait for shutdown to take effect before
+/// giving up on it.
+const SHUTDOWN_WAIT_CYCLES: usize = 32;
+
 const AON_PMCTL_BASE: StaticRef<AonPmCtlRegisters> =
     unsafe { StaticRef::new(0x4009_0000 as *const AonPmCtlRegisters) };
 const AON_IOC_BASE: StaticRef<AonIocRegisters> =
@@ -107,7 +158,7 @@ impl Aon {
         let regs = &*self.event_regs;
 
         // Default to no events at all
-        regs.aux_wu_sel.set(0x3F3F3F3F);
+        regs.mcu_wu_sel1.set(0x3F3F3F3F);
 
         // Set RTC CH1 as a wakeup source by default
         regs.mcu_wu_sel.set(0x3F3F3F24);
@@ -124,6 +175,71 @@ impl Aon {
         regs.event_to_mcu_sel.set(0x003F3F3F);
     }
 
+    /// Replace the set of events that wake the MCU with `sources`. Returns
+    /// ESIZE, leaving the set unchanged, if more than `NUM_WAKEUP_SOURCES` are
+    /// given.
+    pub fn set_wakeup_sources(&self, sources: &[WakeupSource]) -> ReturnCode {
+        if sources.len() > NUM_WAKEUP_SOURCES {
+            return ReturnCode::ESIZE;
+        }
+        for slot in 0..NUM_WAKEUP_SOURCES {
+            let event = sources
+                .get(slot)
+                .map_or(WAKEUP_NONE, |source| *source as u32);
+            self.set_wakeup_slot(slot, event);
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Add `source` to the events that wake the MCU. Returns ENOMEM if all
+    /// wake-up slots are in use.
+    pub fn add_wakeup_source(&self, source: WakeupSource) -> ReturnCode {
+        if self.is_wakeup_source(source) {
+            return ReturnCode::SUCCESS;
+        }
+        match (0..NUM_WAKEUP_SOURCES).find(|&slot| self.wakeup_slot(slot) == WAKEUP_NONE) {
+            Some(slot) => {
+                self.set_wakeup_slot(slot, source as u32);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOMEM,
+        }
+    }
+
+    /// Stop `source` from waking the MCU.
+    pub fn remove_wakeup_source(&self, source: WakeupSource) {
+        for slot in 0..NUM_WAKEUP_SOURCES {
+            if self.wakeup_slot(slot) == source as u32 {
+                self.set_wakeup_slot(slot, WAKEUP_NONE);
+            }
+        }
+    }
+
+    pub fn is_wakeup_source(&self, source: WakeupSource) -> bool {
+        (0..NUM_WAKEUP_SOURCES).any(|slot| self.wakeup_slot(slot) == source as u32)
+    }
+
+    /// Each selector register holds four 6-bit event numbers, one per byte.
+    fn wakeup_register(&self, slot: usize) -> &ReadWrite<u32> {
+        let regs = &*self.event_regs;
+        if slot < 4 {
+            &regs.mcu_wu_sel
+        } else {
+            &regs.mcu_wu_sel1
+        }
+    }
+
+    fn wakeup_slot(&self, slot: usize) -> u32 {
+        let shift = (slot % 4) * 8;
+        (self.wakeup_register(slot).get() >> shift) & WAKEUP_NONE
+    }
+
+    fn set_wakeup_slot(&self, slot: usize, event: u32) {
+        let shift = (slot % 4) * 8;
+        let register = self.wakeup_register(slot);
+        register.set((register.get() & !(WAKEUP_NONE << shift)) | (event << shift));
+    }
+
     pub fn set_dcdc_enabled(&self, enabled: bool) {
         let regs = AON_PMCTL_BASE;
         if enabled {
@@ -166,9 +282,57 @@ impl Aon {
         });
     }
 
-    pub fn shutdown(&self) {
+    /// Latch the state of all IO pads, so that they keep their current
+    /// levels while the rest of the chip is powered down or reconfigured.
+    pub fn io_freeze(&self, freeze: bool) {
         let regs = AON_PMCTL_BASE;
-        regs.shutdown.modify(Shutdown::PWR_DWN_DIS::SET);
+        if freeze {
+            regs.sleep_ctl.write(SleepCtl::IO_PAD_SLEEP_DIS::CLEAR);
+        } else {
+            regs.sleep_ctl.write(SleepCtl::IO_PAD_SLEEP_DIS::SET);
+        }
+    }
+
+    /// Whether this boot is a wake-up from shutdown rather than a cold boot.
+    pub fn woke_from_shutdown(&self) -> bool {
+        let regs = AON_PMCTL_BASE;
+        regs.reset_ctl.is_set(ResetCtl::WU_FROM_SD)
+    }
+
+    /// Whether this boot is a wake-up from shutdown caused by an IO.
+    pub fn woke_from_shutdown_by_io(&self) -> bool {
+        let regs = AON_PMCTL_BASE;
+        regs.reset_ctl.is_set(ResetCtl::GPIO_WU_FROM_SD)
+    }
+
+    /// Enter shutdown, the lowest power mode. All IO pads are latched and
+    /// everything except the IO wake-up logic is powered off. The chip only
+    /// leaves shutdown through a reset, caused by a pin configured with
+    /// `GPIOPin::enable_shutdown_wakeup()` or the reset pin. The reset is a
+    /// full boot, in which `woke_from_shutdown()` returns true.
+    ///
+    /// Returns EBUSY without entering shutdown if a wake-up pin is already at
+    /// its wake-up level, since the chip would wake again immediately.
+    /// Shutdown is not entered while a debugger holds the JTAG domain on. If
+    /// the chip is still running after `SHUTDOWN_WAIT_CYCLES` AON cycles, the
+    /// pads are released again and FAIL is returned.
+    pub fn shutdown(&self) -> ReturnCode {
+        if unsafe { gpio::PORT.shutdown_wakeup_asserted() } {
+            return ReturnCode::EBUSY;
+        }
+
+        let regs = AON_PMCTL_BASE;
+        self.io_freeze(true);
+        self.sync();
+        regs.shutdown.write(Shutdown::EN::SET);
+        for _ in 0..SHUTDOWN_WAIT_CYCLES {
+            self.sync();
+        }
+
+        // Still running, so shutdown was refused
+        regs.shutdown.write(Shutdown::EN::CLEAR);
+        self.io_freeze(false);
+        ReturnCode::FAIL
     }
     /// Await a cycle of the AON domain in order
     /// to sync with it.
@@ -176,3 +340,9 @@ impl Aon {
         unsafe { rtc::RTC.sync() };
     }
 }
+
+impl hil::power::Shutdown for Aon {
+    fn shutdown(&self) -> ReturnCode {
+        Aon::shutdown(self)
+    }
+}
@@ -38,15 +38,16 @@ impl kernel::Chip for Cc26X2 {
         unsafe {
             while let Some(event) = events::next_pending() {
                 events::clear_event_flag(event);
-                match event {
+                cortexm4::latency::service_event(|| match event {
                     EVENT_PRIORITY::GPIO => gpio::PORT.handle_events(),
                     EVENT_PRIORITY::AON_RTC => rtc::RTC.handle_events(),
                     EVENT_PRIORITY::I2C0 => i2c::I2C0.handle_events(),
                     EVENT_PRIORITY::UART0 => uart::UART0.handle_events(),
                     EVENT_PRIORITY::UART1 => uart::UART1.handle_events(),
+                    EVENT_PRIORITY::SWEV => events::handle_software_event(),
                     EVENT_PRIORITY::AON_PROG => (),
                     _ => panic!("unhandled event {:?} ", event),
-                }
+                });
             }
         }
     }
@@ -1,9 +1,6 @@
-use cortexm4::{
-    disable_specific_nvic, generic_isr, hard_fault_handler, nvic, set_privileged_thread,
-    stash_process_state, svc_handler, systick_handler,
-};
+use cortexm4::{hard_fault_handler, nvic, svc_handler, systick_handler};
 
-use events::set_event_flag_from_isr;
+use peripheral_interrupts::{self, NUM_IRQS};
 
 extern "C" {
     // Symbols defined in the linker file
@@ -19,106 +16,93 @@ extern "C" {
     fn _estack();
 }
 
-use event_priority;
-macro_rules! generic_isr {
-    ($label:tt, $priority:expr) => {
-        #[cfg(target_os = "none")]
-        #[naked]
-        unsafe extern "C" fn $label() {
-            stash_process_state();
-            set_event_flag_from_isr($priority);
-            disable_specific_nvic();
-            set_privileged_thread();
-        }
-    };
-}
-
-macro_rules! custom_isr {
-    ($label:tt, $priority:expr, $isr:ident) => {
-        #[cfg(target_os = "none")]
-        #[naked]
-        unsafe extern "C" fn $label() {
-            stash_process_state();
-            set_event_flag_from_isr($priority);
-            $isr();
-            set_privileged_thread();
-        }
-    };
+/// Every peripheral interrupt enters the kernel here. The interrupt number is
+/// read from IPSR and looked up in the table in `peripheral_interrupts`,
+/// which decides which event flag to set and whether a handler runs in
+/// interrupt context.
+///
+/// Interrupts can have different priorities, so this handler may preempt
+/// another one. Only an interrupt taken while a process was running saves the
+/// process registers and returns to the kernel in privileged thread mode, as
+/// `stash_process_state` and `set_privileged_thread` do. Any other interrupt
+/// returns to wherever it was taken, with its EXC_RETURN untouched, so a
+/// nested interrupt resumes the handler it preempted.
+#[cfg(target_os = "none")]
+#[naked]
+unsafe extern "C" fn dispatch_isr() {
+    asm!(
+        "
+    cmp lr, #0xfffffffd
+    bne 1f
+    /* Interrupted a process: save r4-r11 to the Process struct's stored */
+    /* registers, which the kernel's r1 points to. The kernel's r1 lives in */
+    /* the second word of the hardware stacked registers on MSP */
+    mov r1, sp
+    ldr r1, [r1, #4]
+    stmia r1, {r4-r11}
+    bl cc26x2_dispatch_active_irq
+    /* Return to the kernel in privileged thread mode */
+    mov r0, #0
+    msr CONTROL, r0
+    movw lr, #0xfff9
+    movt lr, #0xffff
+    bx lr
+  1:
+    /* Interrupted the kernel or another handler: return there unchanged */
+    push {r4, lr}
+    bl cc26x2_dispatch_active_irq
+    pop {r4, lr}
+    bx lr"
+    : : : : "volatile" );
 }
 
-generic_isr!(gpio_nvic, event_priority::EVENT_PRIORITY::GPIO);
-generic_isr!(i2c0_nvic, event_priority::EVENT_PRIORITY::I2C0);
-generic_isr!(aon_rtc_nvic, event_priority::EVENT_PRIORITY::AON_RTC);
+#[cfg(not(target_os = "none"))]
+unsafe extern "C" fn dispatch_isr() {}
 
-use uart::{uart0_isr, uart1_isr};
-custom_isr!(uart0_nvic, event_priority::EVENT_PRIORITY::UART0, uart0_isr);
-custom_isr!(uart1_nvic, event_priority::EVENT_PRIORITY::UART1, uart1_isr);
+#[cfg(target_os = "none")]
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn cc26x2_dispatch_active_irq() {
+    let ipsr: u32;
+    asm!("mrs $0, IPSR" : "=r"(ipsr) : : : "volatile");
+    // Peripheral interrupts start after the 16 system exceptions
+    peripheral_interrupts::dispatch((ipsr & 0xff) - 16);
+}
 
 unsafe extern "C" fn unhandled_interrupt() {
     'loop0: loop {}
 }
 
+#[repr(C)]
+pub struct VectorTable {
+    system: [unsafe extern "C" fn(); 16],
+    peripheral: [unsafe extern "C" fn(); NUM_IRQS],
+}
+
 #[link_section = ".vectors"]
 // used Ensures that the symbol is kept until the final binary
 #[used]
-pub static BASE_VECTORS: [unsafe extern "C" fn(); 54] = [
-    _estack,
-    reset_handler,
-    unhandled_interrupt, // NMI
-    hard_fault_handler,  // Hard Fault
-    unhandled_interrupt, // MPU fault
-    unhandled_interrupt, // Bus fault
-    unhandled_interrupt, // Usage fault
-    unhandled_interrupt, // Reserved
-    unhandled_interrupt, // Reserved
-    unhandled_interrupt, // Reserved
-    unhandled_interrupt, // Reserved
-    svc_handler,         // SVC
-    unhandled_interrupt, // Debug monitor,
-    unhandled_interrupt, // Reserved
-    unhandled_interrupt, // PendSV
-    systick_handler,     // Systick
-    gpio_nvic,           // GPIO Int handler
-    i2c0_nvic,           // I2C0
-    generic_isr,         // RF Core Command & Packet Engine 1
-    generic_isr,         // AON SpiSplave Rx, Tx and CS
-    aon_rtc_nvic,        // AON RTC
-    uart0_nvic,          // UART0 Rx and Tx
-    generic_isr,         // AUX software event 0
-    generic_isr,         // SSI0 Rx and Tx
-    generic_isr,         // SSI1 Rx and Tx
-    generic_isr,         // RF Core Command & Packet Engine 0
-    generic_isr,         // RF Core Hardware
-    generic_isr,         // RF Core Command Acknowledge
-    generic_isr,         // I2S
-    generic_isr,         // AUX software event 1
-    generic_isr,         // Watchdog timer
-    generic_isr,         // Timer 0 subtimer A
-    generic_isr,         // Timer 0 subtimer B
-    generic_isr,         // Timer 1 subtimer A
-    generic_isr,         // Timer 1 subtimer B
-    generic_isr,         // Timer 2 subtimer A
-    generic_isr,         // Timer 2 subtimer B
-    generic_isr,         // Timer 3 subtimer A
-    generic_isr,         // Timer 3 subtimer B
-    generic_isr,         // Crypto Core Result available
-    generic_isr,         // uDMA Software
-    generic_isr,         // uDMA Error
-    generic_isr,         // Flash controller
-    generic_isr,         // Software Event 0
-    generic_isr,         // AUX combined event
-    generic_isr,         // AON programmable 0
-    generic_isr,         // Dynamic Programmable interrupt
-    // source (Default: PRCM)
-    generic_isr, // AUX Comparator A
-    generic_isr, // AUX ADC new sample or ADC DMA
-    // done, ADC underflow, ADC overflow
-    generic_isr, // TRNG event (hw_ints.h 49)
-    generic_isr,
-    generic_isr,
-    uart1_nvic, //uart1_generic_isr,//uart::uart1_isr, // 52 allegedly UART1 (http://e2e.ti.com/support/wireless_connectivity/proprietary_sub_1_ghz_simpliciti/f/156/t/662981?CC1312R-UART1-can-t-work-correctly-in-sensor-oad-cc1312lp-example-on-both-cc1312-launchpad-and-cc1352-launchpad)
-    generic_isr,
-];
+pub static BASE_VECTORS: VectorTable = VectorTable {
+    system: [
+        _estack,
+        reset_handler,
+        unhandled_interrupt, // NMI
+        hard_fault_handler,  // Hard Fault
+        unhandled_interrupt, // MPU fault
+        unhandled_interrupt, // Bus fault
+        unhandled_interrupt, // Usage fault
+        unhandled_interrupt, // Reserved
+        unhandled_interrupt, // Reserved
+        unhandled_interrupt, // Reserved
+        unhandled_interrupt, // Reserved
+        svc_handler,         // SVC
+        unhandled_interrupt, // Debug monitor,
+        unhandled_interrupt, // Reserved
+        unhandled_interrupt, // PendSV
+        systick_handler,     // Systick
+    ],
+    peripheral: [dispatch_isr; NUM_IRQS],
+};
 
 #[no_mangle]
 pub unsafe extern "C" fn init() {
@@ -166,5 +150,6 @@ pub unsafe extern "C" fn init() {
             _old
         } = 0u32;
     }
+    peripheral_interrupts::set_default_handlers();
     nvic::enable_all();
 }
@@ -2,9 +2,11 @@
 //  These are configurable priorities that can be used by ISRs or yields from within kernel space
 //
 
+use cortexm::support::Primask;
 use enum_primitive::cast::FromPrimitive;
+use kernel::common::stats::Flags;
 
-pub static mut FLAGS: u32 = 0;
+pub static mut FLAGS: Flags<Primask> = Flags::new();
 
 enum_from_primitive!{
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -14,6 +16,7 @@ pub enum EVENT_PRIORITY {
     UART1 = 1,
     AON_RTC = 3,
     RTC = 4,
+    SWEV = 5,
     I2C0 = 6,
     AON_PROG = 7,
 }
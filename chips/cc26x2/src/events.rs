@@ -2,71 +2,51 @@
 //  These are generic event handling routines which could be defined in cortexm
 //
 
-use core::ptr;
-use cortexm::support::atomic;
+use cortexm4::nvic;
 use enum_primitive::cast::FromPrimitive;
 use event_priority::{EVENT_PRIORITY, FLAGS};
+use peripheral_interrupts;
+
+const SWEV_NVIC: nvic::Nvic =
+    unsafe { nvic::Nvic::new(peripheral_interrupts::NVIC_IRQ::SW_EVENT0 as u32) };
 
 pub fn has_event() -> bool {
-    let event_flags;
-    unsafe { event_flags = ptr::read_volatile(&FLAGS) }
-    event_flags != 0
+    unsafe { FLAGS.any() }
 }
 
 pub fn next_pending() -> Option<EVENT_PRIORITY> {
-    let mut event_flags;
-    unsafe { event_flags = ptr::read_volatile(&FLAGS) }
-
-    let mut count = 0;
-    // stay in loop until we found the flag
-    while event_flags != 0 {
-        // if flag is found, return the count
-        if (event_flags & 0b1) != 0 {
-            return Some(EVENT_PRIORITY::from_u8(count).expect("Unmapped EVENT_PRIORITY"));
-        }
-        // otherwise increment
-        count += 1;
-        event_flags >>= 1;
-    }
-    None
+    unsafe { FLAGS.first() }
+        .map(|flag| EVENT_PRIORITY::from_u8(flag).expect("Unmapped EVENT_PRIORITY"))
 }
 
 #[inline(never)]
 pub fn set_event_flag(priority: EVENT_PRIORITY) {
-    unsafe {
-        let bm = 0b1 << (priority as u8) as u32;
-        atomic(|| {
-            let new_value = ptr::read_volatile(&FLAGS) | bm;
-            FLAGS = new_value;
-        })
-    };
+    unsafe { FLAGS.set(priority as u8) };
 }
 
-#[naked]
 pub unsafe fn set_event_flag_from_isr(priority: EVENT_PRIORITY) {
-    // Set PRIMASK
-    asm!("cpsid i" :::: "volatile");
+    FLAGS.set(priority as u8);
+}
 
-    asm!("
-        // Set event flag
-        orr $0, $2
-        isb
-        "
-        : "={r0}"(FLAGS)
-        : "{r0}"(FLAGS), "{r1}"(0b1<<(priority as u8))
-        : : "volatile" "volatile"
-    );
+pub fn clear_event_flag(priority: EVENT_PRIORITY) {
+    unsafe { FLAGS.clear(priority as u8) };
+}
 
-    // Unset PRIMASK
-    asm!("cpsie i" :::: "volatile");
+/// Set an event flag from thread context and make sure the kernel loop
+/// services it.
+///
+/// This pends the Software Event 0 interrupt, whose handler sets the `SWEV`
+/// flag. Because it is a real interrupt, it also wakes the core if the
+/// kernel has already decided to sleep, so drivers can defer work to their
+/// `handle_events()` without busy polling.
+pub fn set_event_flag_and_wake(priority: EVENT_PRIORITY) {
+    set_event_flag(priority);
+    SWEV_NVIC.set_pending();
 }
 
-pub fn clear_event_flag(priority: EVENT_PRIORITY) {
-    unsafe {
-        let bm = !0b1 << (priority as u8) as u32;
-        atomic(|| {
-            let new_value = ptr::read_volatile(&FLAGS) & bm;
-            FLAGS = new_value;
-        })
-    };
+/// Re-arm the Software Event 0 interrupt after its `SWEV` flag has been
+/// serviced.
+pub fn handle_software_event() {
+    SWEV_NVIC.clear_pending();
+    SWEV_NVIC.enable();
 }
@@ -11,12 +11,16 @@ use kernel::common::registers::{ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil;
 use kernel::hil::gpio::PinCtl;
+use kernel::ReturnCode;
 
 use cortexm4::nvic;
 use peripheral_interrupts;
 
 const NUM_PINS: usize = 32;
 
+/// The only pins that can use the 4/8mA drive strength (IOCURR = 0b10).
+const HIGH_DRIVE_PINS: [usize; 3] = [5, 6, 7];
+
 const GPIO_BASE: StaticRef<GpioRegisters> =
     unsafe { StaticRef::new(0x40022000 as *const GpioRegisters) };
 
@@ -39,6 +43,13 @@ struct GpioRegisters {
     pub evflags: ReadWrite<u32>,
 }
 
+/// The input level that wakes the chip from shutdown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WakeupLevel {
+    Low,
+    High,
+}
+
 pub struct GPIOPin {
     registers: StaticRef<GpioRegisters>,
     ioc_registers: StaticRef<IocRegisters>,
@@ -79,7 +90,13 @@ struct IocRegisters {
 register_bitfields![
     u32,
     IoConfiguration [
+        HYST_EN     OFFSET(30) NUMBITS(1) [], // Input hysteresis enable
         IE          OFFSET(29) NUMBITS(1) [], // Input Enable
+        WU_CFG      OFFSET(27) NUMBITS(2) [   // Wake-up from shutdown
+            None         = 0b00,
+            WakeupLow    = 0b10,
+            WakeupHigh   = 0b11
+        ],
         IO_MODE     OFFSET(24) NUMBITS(3) [],
         EDGE_IRQ_EN OFFSET(18) NUMBITS(1) [], // Interrupt enable
         EDGE_DET    OFFSET(16) NUMBITS(2) [
@@ -93,6 +110,19 @@ register_bitfields![
             PullUp   = 0b10,
             PullNone = 0b11
         ],
+        SLEW_RED    OFFSET(12) NUMBITS(1) [], // Reduced output slew rate
+        IOCURR      OFFSET(10) NUMBITS(2) [
+            Current2mA   = 0b00,
+            Current4mA   = 0b01,
+            // Only available on the high-drive pins
+            Current4_8mA = 0b10
+        ],
+        IOSTR       OFFSET(8) NUMBITS(2) [
+            Auto    = 0b00,
+            Minimum = 0b01,
+            Medium  = 0b10,
+            Maximum = 0b11
+        ],
         PORT_ID     OFFSET(0) NUMBITS(6) [
             // From p.1072
             GPIO = 0,
@@ -144,8 +174,17 @@ impl GPIOPin {
         let pin_ioc = &self.ioc_registers.iocfg[self.pin];
 
         // In order to configure the pin for GPIO we need to clear
-        // the lower 6 bits.
-        pin_ioc.write(IoConfiguration::PORT_ID::GPIO);
+        // the lower 6 bits. The drive strength, slew rate, hysteresis and
+        // shutdown wake-up settings are kept so that a board's configuration
+        // survives the pin being (re)configured as a GPIO by a capsule.
+        pin_ioc.write(
+            IoConfiguration::PORT_ID::GPIO
+                + IoConfiguration::IOSTR.val(pin_ioc.read(IoConfiguration::IOSTR))
+                + IoConfiguration::IOCURR.val(pin_ioc.read(IoConfiguration::IOCURR))
+                + IoConfiguration::SLEW_RED.val(pin_ioc.read(IoConfiguration::SLEW_RED))
+                + IoConfiguration::HYST_EN.val(pin_ioc.read(IoConfiguration::HYST_EN))
+                + IoConfiguration::WU_CFG.val(pin_ioc.read(IoConfiguration::WU_CFG)),
+        );
     }
 
     pub fn enable_output(&self) {
@@ -177,6 +216,33 @@ impl GPIOPin {
         pin_ioc.modify(IoConfiguration::EDGE_IRQ_EN::CLEAR);
     }
 
+    /// Wake the chip from shutdown when this input goes to `level`.
+    pub fn enable_shutdown_wakeup(&self, level: WakeupLevel) {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+        match level {
+            WakeupLevel::Low => pin_ioc.modify(IoConfiguration::WU_CFG::WakeupLow),
+            WakeupLevel::High => pin_ioc.modify(IoConfiguration::WU_CFG::WakeupHigh),
+        }
+        self.enable_input();
+    }
+
+    pub fn disable_shutdown_wakeup(&self) {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+        pin_ioc.modify(IoConfiguration::WU_CFG::None);
+    }
+
+    /// Whether this pin is configured to wake the chip from shutdown and is
+    /// currently at its wake-up level.
+    fn shutdown_wakeup_asserted(&self) -> bool {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+        let high = self.registers.din.get() & self.pin_mask != 0;
+        match pin_ioc.read(IoConfiguration::WU_CFG) {
+            0b10 => !high,
+            0b11 => high,
+            _ => false,
+        }
+    }
+
     /// Configures pin for I2C SDA
     pub fn enable_i2c_sda(&self) {
         let pin_ioc = &self.ioc_registers.iocfg[self.pin];
@@ -254,6 +320,51 @@ impl hil::gpio::PinCtl for GPIOPin {
 
         pin_ioc.modify(field);
     }
+
+    fn set_drive_strength(&self, strength: hil::gpio::DriveStrength) -> ReturnCode {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+
+        let field = match strength {
+            hil::gpio::DriveStrength::Low => {
+                IoConfiguration::IOCURR::Current2mA + IoConfiguration::IOSTR::Minimum
+            }
+            hil::gpio::DriveStrength::Medium => {
+                IoConfiguration::IOCURR::Current4mA + IoConfiguration::IOSTR::Medium
+            }
+            hil::gpio::DriveStrength::High => {
+                if !HIGH_DRIVE_PINS.contains(&self.pin) {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                IoConfiguration::IOCURR::Current4_8mA + IoConfiguration::IOSTR::Maximum
+            }
+        };
+
+        pin_ioc.modify(field);
+        ReturnCode::SUCCESS
+    }
+
+    fn set_slew_rate(&self, rate: hil::gpio::SlewRate) -> ReturnCode {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+
+        let field = match rate {
+            hil::gpio::SlewRate::Fast => IoConfiguration::SLEW_RED::CLEAR,
+            hil::gpio::SlewRate::Reduced => IoConfiguration::SLEW_RED::SET,
+        };
+
+        pin_ioc.modify(field);
+        ReturnCode::SUCCESS
+    }
+
+    fn set_hysteresis(&self, enabled: bool) -> ReturnCode {
+        let pin_ioc = &self.ioc_registers.iocfg[self.pin];
+
+        if enabled {
+            pin_ioc.modify(IoConfiguration::HYST_EN::SET);
+        } else {
+            pin_ioc.modify(IoConfiguration::HYST_EN::CLEAR);
+        }
+        ReturnCode::SUCCESS
+    }
 }
 
 impl hil::gpio::Pin for GPIOPin {
@@ -325,6 +436,12 @@ impl IndexMut<usize> for Port {
 }
 
 impl Port {
+    /// Whether any pin configured to wake the chip from shutdown is at its
+    /// wake-up level.
+    pub fn shutdown_wakeup_asserted(&self) -> bool {
+        self.pins.iter().any(|pin| pin.shutdown_wakeup_asserted())
+    }
+
     pub fn handle_events(&self) {
         let regs = GPIO_BASE;
         let evflags = regs.evflags.get();
@@ -333,14 +450,13 @@ impl Port {
 
         // evflags indicate which pins has triggered an interrupt,
         // we need to call the respective handler for positive bit in evflags.
-        let mut pin: usize = usize::max_value();
-        while pin < self.pins.len() {
-            pin = evflags.trailing_zeros() as usize;
-            if pin >= self.pins.len() {
-                break;
+        let mut evflags = evflags;
+        while evflags != 0 {
+            let pin = evflags.trailing_zeros() as usize;
+            evflags &= !(1 << pin);
+            if pin < self.pins.len() {
+                self.pins[pin].handle_interrupt();
             }
-
-            self.pins[pin].handle_interrupt();
         }
         self.nvic.clear_pending();
         self.nvic.enable();
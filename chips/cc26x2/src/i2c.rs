@@ -235,20 +235,27 @@ impl<'a> I2CMaster<'a> {
                 }
                 TransferMode::TransmitThenReceive(read_len) => {
                     transfer.index += 1;
+                    let read_len = cmp::min(read_len, transfer.buf.len());
                     if transfer.len > transfer.index {
                         self.write_byte(
                             transfer.buf[transfer.index],
                             false,
-                            transfer.len == transfer.index + 1,
+                            read_len == 0 && transfer.len == transfer.index + 1,
                         );
                         self.transfer.put(transfer);
-                    } else {
+                    } else if read_len > 0 {
+                        // The bus is still held, so setting START again
+                        // issues a repeated start for the read.
                         transfer.index = 0;
-                        transfer.len = cmp::min(read_len, transfer.buf.len());
+                        transfer.len = read_len;
                         transfer.mode = TransferMode::Receive;
                         self.registers.msa.modify(Address::RS::Receive);
                         self.read_byte(true, transfer.len == transfer.index + 1);
                         self.transfer.put(transfer);
+                    } else {
+                        self.client.map(move |client| {
+                            client.command_complete(transfer.buf, i2c::Error::CommandComplete)
+                        });
                     }
                 }
             }
@@ -302,7 +309,8 @@ impl<'a> i2c::I2CMaster for I2CMaster<'a> {
             .write(Address::RS::Transmit + Address::SA.val(addr as u32));
         let len = cmp::min(write_len as usize, data.len());
         if len > 0 {
-            self.write_byte(data[0], true, len == 1);
+            // Only stop after the write if there is nothing to read back
+            self.write_byte(data[0], true, len == 1 && read_len == 0);
             self.transfer.put(Transfer {
                 mode: TransferMode::TransmitThenReceive(read_len as usize),
                 buf: data,
@@ -1,4 +1,4 @@
-#![feature(const_fn, untagged_unions, used, asm, naked_functions)]
+#![feature(const_fn, untagged_unions, used, asm, naked_functions, crate_visibility_modifier)]
 #![no_std]
 #![crate_name = "cc26x2"]
 #![crate_type = "rlib"]
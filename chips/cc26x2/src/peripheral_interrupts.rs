@@ -1,7 +1,15 @@
+use cortexm4::nvic;
 use enum_primitive::cast::FromPrimitive;
+use event_priority::EVENT_PRIORITY;
+use events::set_event_flag_from_isr;
+use uart::{uart0_isr, uart1_isr};
+
+/// Number of peripheral interrupt lines, following the 16 Cortex-M system
+/// exceptions in the vector table.
+pub const NUM_IRQS: usize = 38;
 
 enum_from_primitive!{
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum NVIC_IRQ {
     GPIO = 0,
     I2C0 = 1,
@@ -39,3 +47,82 @@ pub enum NVIC_IRQ {
     UART1 = 36
 }
 }
+
+/// Apply a board-supplied interrupt priority map.
+///
+/// Every interrupt defaults to priority 0, the highest, so no interrupt can
+/// preempt another. Boards use this to let time-critical interrupts preempt
+/// long-running handlers. The cc26x2 implements three priority bits, so the
+/// usable priorities are 0x00, 0x20, ..., 0xE0, with lower values preempting
+/// higher ones.
+///
+/// Nested interrupts are handled by `crt1::dispatch_isr`, which only returns
+/// to thread mode from the outermost handler.
+pub unsafe fn set_priorities(priorities: &[(NVIC_IRQ, u8)]) {
+    for &(irq, priority) in priorities.iter() {
+        nvic::Nvic::new(irq as u32).set_priority(priority);
+    }
+}
+
+/// What the kernel does when a peripheral interrupt fires.
+#[derive(Clone, Copy)]
+pub struct IrqHandler {
+    /// Event flag set for the kernel loop to service, if any.
+    event: Option<EVENT_PRIORITY>,
+    /// Called from the interrupt itself, before returning to the kernel.
+    /// Without one, the interrupt is disabled in the NVIC until the driver
+    /// has serviced the event and re-enables it.
+    isr: Option<extern "C" fn()>,
+}
+
+impl IrqHandler {
+    const NONE: IrqHandler = IrqHandler {
+        event: None,
+        isr: None,
+    };
+}
+
+static mut HANDLERS: [IrqHandler; NUM_IRQS] = [IrqHandler::NONE; NUM_IRQS];
+
+/// Map an interrupt to the event flag the kernel loop services for it.
+pub unsafe fn set_event(irq: NVIC_IRQ, event: EVENT_PRIORITY) {
+    HANDLERS[irq as usize] = IrqHandler {
+        event: Some(event),
+        isr: None,
+    };
+}
+
+/// Map an interrupt to an event flag, and handle it in interrupt context
+/// with `isr` first. `isr` must clear the interrupt source, since the
+/// interrupt stays enabled.
+pub unsafe fn set_isr(irq: NVIC_IRQ, event: EVENT_PRIORITY, isr: extern "C" fn()) {
+    HANDLERS[irq as usize] = IrqHandler {
+        event: Some(event),
+        isr: Some(isr),
+    };
+}
+
+/// Install the chip's default interrupt to event mapping. Called from
+/// `init()`; boards can then override entries with `set_event()` and
+/// `set_isr()`.
+crate unsafe fn set_default_handlers() {
+    set_event(NVIC_IRQ::GPIO, EVENT_PRIORITY::GPIO);
+    set_event(NVIC_IRQ::I2C0, EVENT_PRIORITY::I2C0);
+    set_event(NVIC_IRQ::AON_RTC, EVENT_PRIORITY::AON_RTC);
+    set_event(NVIC_IRQ::SW_EVENT0, EVENT_PRIORITY::SWEV);
+    set_isr(NVIC_IRQ::UART0, EVENT_PRIORITY::UART0, uart0_isr);
+    set_isr(NVIC_IRQ::UART1, EVENT_PRIORITY::UART1, uart1_isr);
+}
+
+/// Handle the peripheral interrupt `irq`, as read from IPSR by the vector
+/// table's dispatch handler.
+crate unsafe fn dispatch(irq: u32) {
+    let handler = HANDLERS
+        .get(irq as usize)
+        .map_or(IrqHandler::NONE, |handler| *handler);
+    handler.event.map(|event| set_event_flag_from_isr(event));
+    match handler.isr {
+        Some(isr) => isr(),
+        None => nvic::Nvic::new(irq).disable(),
+    }
+}
@@ -1,18 +1,26 @@
 //! UART driver, cc26x2 family
 use kernel;
-use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::cells::{MapCell, OptionalCell, VolatileCell};
 use kernel::common::registers::{ReadOnly, ReadWrite, WriteOnly};
-use kernel::common::StaticRef;
+use kernel::common::stats::Counter;
+use kernel::common::{CircularBuffer, StaticRef};
 use kernel::hil::uart;
 use kernel::ReturnCode;
 
 use core::cmp;
 use cortexm4::nvic;
+use cortexm4::support::Primask;
+use event_priority::EVENT_PRIORITY;
+use events;
 use peripheral_interrupts;
 use prcm;
 
 const MCU_CLOCK: u32 = 48_000_000;
 
+/// Size of the software RX buffer that bytes are moved into from the
+/// hardware FIFO by the interrupt handler. Must be a power of two.
+const RX_RING_LEN: usize = 64;
+
 #[repr(C)]
 struct UartRegisters {
     dr: ReadWrite<u32>,
@@ -32,8 +40,21 @@ struct UartRegisters {
     dmactl: ReadWrite<u32>,
 }
 
-pub static mut UART0: UART = UART::new(&UART0_BASE, &UART0_NVIC);
-pub static mut UART1: UART = UART::new(&UART1_BASE, &UART1_NVIC);
+static mut UART0_RX_RING: [VolatileCell<u8>; RX_RING_LEN] = [VolatileCell::new(0); RX_RING_LEN];
+static mut UART1_RX_RING: [VolatileCell<u8>; RX_RING_LEN] = [VolatileCell::new(0); RX_RING_LEN];
+
+pub static mut UART0: UART = UART::new(
+    &UART0_BASE,
+    &UART0_NVIC,
+    EVENT_PRIORITY::UART0,
+    unsafe { &UART0_RX_RING },
+);
+pub static mut UART1: UART = UART::new(
+    &UART1_BASE,
+    &UART1_NVIC,
+    EVENT_PRIORITY::UART1,
+    unsafe { &UART1_RX_RING },
+);
 
 register_bitfields![
     u32,
@@ -110,10 +131,19 @@ struct Transaction {
 pub struct UART {
     registers: &'static StaticRef<UartRegisters>,
     nvic: &'static nvic::Nvic,
+    /// Event flag serviced by `handle_events`
+    event: EVENT_PRIORITY,
     tx_client: OptionalCell<&'static uart::Client>,
     rx_client: OptionalCell<&'static uart::Client>,
     tx: MapCell<Transaction>,
     rx: MapCell<Transaction>,
+    /// Bytes received by the interrupt handler that have not yet been copied
+    /// into a client's buffer
+    rx_ring: CircularBuffer<'static, u8>,
+    /// Number of bytes read from the RX FIFO
+    rx_bytes: Counter<Primask>,
+    /// Number of received bytes dropped because the RX ring was full
+    rx_dropped: Counter<Primask>,
 }
 
 macro_rules! uart_nvic {
@@ -121,17 +151,15 @@ macro_rules! uart_nvic {
         #[inline(never)]
         pub extern "C" fn $fn_name() {
             unsafe {
-                // handle RX
-                $uart.rx.map(|rx| {
-                    while $uart.rx_fifo_not_empty() && rx.index < rx.length {
-                        let byte = $uart.read_byte();
-                        rx.buffer[rx.index] = byte;
-                        rx.index += 1;
+                // handle RX: move the FIFO into the ring buffer, bytes are
+                // copied to the client in handle_events. If the ring is
+                // full the byte is dropped.
+                while $uart.rx_fifo_not_empty() {
+                    let byte = $uart.read_byte();
+                    $uart.rx_bytes.increment();
+                    if !$uart.rx_ring.push(byte) {
+                        $uart.rx_dropped.increment();
                     }
-                });
-                // if there is no client, empty the buffer into the void
-                if $uart.rx_fifo_not_empty() {
-                    $uart.read_byte();
                 }
                 $uart.tx.map(|tx| {
                     // if a big buffer was given, this could be a very long call
@@ -151,17 +179,37 @@ uart_nvic!(uart0_isr, UART0);
 uart_nvic!(uart1_isr, UART1);
 
 impl UART {
-    const fn new(registers: &'static StaticRef<UartRegisters>, nvic: &'static nvic::Nvic) -> UART {
+    const fn new(
+        registers: &'static StaticRef<UartRegisters>,
+        nvic: &'static nvic::Nvic,
+        event: EVENT_PRIORITY,
+        rx_ring: &'static [VolatileCell<u8>],
+    ) -> UART {
         UART {
             registers,
             nvic,
+            event,
             tx_client: OptionalCell::empty(),
             rx_client: OptionalCell::empty(),
             tx: MapCell::empty(),
             rx: MapCell::empty(),
+            rx_ring: CircularBuffer::new(rx_ring),
+            rx_bytes: Counter::new(),
+            rx_dropped: Counter::new(),
         }
     }
 
+    /// Number of bytes received since boot.
+    pub fn rx_bytes(&self) -> u32 {
+        self.rx_bytes.get()
+    }
+
+    /// Number of received bytes dropped because no client buffer was
+    /// available and the RX ring was full.
+    pub fn rx_dropped(&self) -> u32 {
+        self.rx_dropped.get()
+    }
+
     /// Initialize the UART hardware.
     ///
     /// This function needs to be run before the UART module is used.
@@ -251,6 +299,7 @@ impl UART {
         self.registers.icr.write(Interrupts::ALL_INTERRUPTS::SET);
 
         self.rx.take().map(|mut rx| {
+            rx.index += self.rx_ring.pop_into(&mut rx.buffer[rx.index..rx.length]);
             if rx.index == rx.length {
                 self.rx_client.map(move |client| {
                     client.receive_complete(
@@ -344,19 +393,22 @@ impl kernel::hil::uart::UART for UART {
     }
 
     fn receive(&self, buffer: &'static mut [u8], len: usize) {
-        if len == 0 {
-            self.rx_client.map(move |client| {
-                client.receive_complete(buffer, len, kernel::hil::uart::Error::CommandComplete);
-            });
-        } else {
-            // if client set len too big, we will receive what we can
-            let rx_len = cmp::min(len, buffer.len());
+        // if client set len too big, we will receive what we can
+        let rx_len = cmp::min(len, buffer.len());
 
-            self.rx.put(Transaction {
-                buffer: buffer,
-                length: rx_len,
-                index: 0,
-            });
+        // Bytes may have arrived before this receive was posted
+        let index = self.rx_ring.pop_into(&mut buffer[..rx_len]);
+
+        self.rx.put(Transaction {
+            buffer: buffer,
+            length: rx_len,
+            index: index,
+        });
+
+        // If the receive is already satisfied, complete it from
+        // handle_events rather than calling back into the client here
+        if index == rx_len {
+            events::set_event_flag_and_wake(self.event);
         }
     }
 
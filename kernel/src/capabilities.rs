@@ -58,3 +58,7 @@ pub unsafe trait MainLoopCapability {}
 /// The `MemoryAllocationCapability` capability allows the holder to allocate
 /// memory, for example by creating grants.
 pub unsafe trait MemoryAllocationCapability {}
+
+/// The `PowerManagementCapability` capability allows the holder to change the
+/// power state of the whole system, for example by shutting it down.
+pub unsafe trait PowerManagementCapability {}
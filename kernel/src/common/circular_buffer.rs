@@ -0,0 +1,141 @@
+//! Single-producer, single-consumer circular buffer that can be shared between
+//! an interrupt handler and the bottom half of a driver.
+//!
+//! Unlike `RingBuffer`, all operations take `&self`: the producer only ever
+//! writes the head index and the consumer only ever writes the tail index,
+//! and both are accessed with volatile reads and writes. An element is
+//! written before the head index that publishes it, so a consumer never
+//! observes a slot that has not been filled. This makes it safe for an ISR to
+//! `push()` while the driver's `handle_events()` `pop()`s, without disabling
+//! interrupts.
+//!
+//! The backing storage must have a power-of-two length. Indices run freely
+//! and are masked on access, so every slot of the storage is usable. The
+//! constructor is a `const fn` so that chip drivers can embed a buffer in
+//! their `static` peripheral structs.
+//!
+//! A client may optionally be registered to be told when the number of
+//! buffered elements reaches a watermark. The callback is made from the
+//! producer's context, which may be an interrupt handler, so clients should
+//! do no more than note the event.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::common::cells::VolatileCell;
+//! # use kernel::common::CircularBuffer;
+//! static mut STORAGE: [VolatileCell<u8>; 16] = [VolatileCell::new(0); 16];
+//!
+//! let ring = CircularBuffer::new(unsafe { &STORAGE });
+//! ring.push(0xAA);
+//! assert_eq!(ring.pop(), Some(0xAA));
+//! ```
+
+use common::cells::{OptionalCell, VolatileCell};
+use core::cell::Cell;
+
+/// Notified when a `CircularBuffer` fills to its watermark.
+pub trait CircularBufferClient {
+    /// Called from `push()` when the number of buffered elements reaches the
+    /// configured watermark.
+    fn watermark_reached(&self, len: usize);
+}
+
+pub struct CircularBuffer<'a, T: 'a + Copy> {
+    ring: &'a [VolatileCell<T>],
+    head: VolatileCell<usize>,
+    tail: VolatileCell<usize>,
+    watermark: Cell<usize>,
+    client: OptionalCell<&'a CircularBufferClient>,
+}
+
+impl<T: Copy> CircularBuffer<'a, T> {
+    /// Create a buffer over `ring`, whose length must be a non-zero power of
+    /// two.
+    pub const fn new(ring: &'a [VolatileCell<T>]) -> CircularBuffer<'a, T> {
+        CircularBuffer {
+            ring: ring,
+            head: VolatileCell::new(0),
+            tail: VolatileCell::new(0),
+            watermark: Cell::new(0),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Register `client` to be notified when `watermark` elements are
+    /// buffered. A watermark of zero disables notifications.
+    pub fn set_watermark(&self, watermark: usize, client: &'a CircularBufferClient) {
+        self.watermark.set(watermark);
+        self.client.set(client);
+    }
+
+    /// The total number of elements the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// The number of elements currently buffered.
+    pub fn len(&self) -> usize {
+        self.head.get().wrapping_sub(self.tail.get())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.get() == self.tail.get()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.ring.len()
+    }
+
+    /// Add an element. Must only be called by the producer. Returns `false`
+    /// and drops `val` if the buffer is full.
+    pub fn push(&self, val: T) -> bool {
+        debug_assert!(self.ring.len().is_power_of_two());
+        let head = self.head.get();
+        if head.wrapping_sub(self.tail.get()) == self.ring.len() {
+            return false;
+        }
+        self.ring[head & (self.ring.len() - 1)].set(val);
+        self.head.set(head.wrapping_add(1));
+
+        let watermark = self.watermark.get();
+        if watermark != 0 && self.len() == watermark {
+            self.client
+                .map(|client| client.watermark_reached(watermark));
+        }
+        true
+    }
+
+    /// Remove the oldest element. Must only be called by the consumer.
+    pub fn pop(&self) -> Option<T> {
+        let tail = self.tail.get();
+        if tail == self.head.get() {
+            return None;
+        }
+        let val = self.ring[tail & (self.ring.len() - 1)].get();
+        self.tail.set(tail.wrapping_add(1));
+        Some(val)
+    }
+
+    /// Copy as many buffered elements as fit into `buf`, returning how many
+    /// were copied. Must only be called by the consumer.
+    pub fn pop_into(&self, buf: &mut [T]) -> usize {
+        let mut count = 0;
+        while count < buf.len() {
+            match self.pop() {
+                Some(val) => {
+                    buf[count] = val;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+
+    /// Discard all buffered elements. Must only be called by the consumer.
+    pub fn clear(&self) {
+        self.tail.set(self.head.get());
+    }
+}
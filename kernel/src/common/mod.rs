@@ -9,16 +9,19 @@
 /// Re-export the tock-register-interface library.
 pub use tock_registers::{macros, registers};
 
+pub mod circular_buffer;
 pub mod deferred_call;
 pub mod list;
 pub mod math;
 pub mod peripherals;
+pub mod stats;
 pub mod utils;
 
 mod queue;
 mod ring_buffer;
 mod static_ref;
 
+pub use self::circular_buffer::CircularBuffer;
 pub use self::list::{List, ListLink, ListNode};
 pub use self::queue::Queue;
 pub use self::ring_buffer::RingBuffer;
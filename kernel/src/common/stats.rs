@@ -0,0 +1,178 @@
+//! Interrupt-safe counters, gauges and flag sets.
+//!
+//! These types are meant to be placed in the `static` structures that chip
+//! drivers share between their interrupt handlers and `handle_events()`, and
+//! for event bookkeeping such as pending-interrupt flags. Values are kept in
+//! `VolatileCell`s so reads always observe the latest update, and every
+//! read-modify-write runs inside a critical section provided by the
+//! architecture through the `Atomic` trait (for example
+//! `cortexm::support::Primask`).
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::common::stats::{Atomic, Counter};
+//! # struct NoInterrupts;
+//! # impl Atomic for NoInterrupts {
+//! #     unsafe fn atomic<F: FnOnce() -> R, R>(f: F) -> R { f() }
+//! # }
+//! static mut RX_BYTES: Counter<NoInterrupts> = Counter::new();
+//!
+//! unsafe {
+//!     RX_BYTES.increment();
+//!     assert_eq!(RX_BYTES.get(), 1);
+//! }
+//! ```
+
+use common::cells::VolatileCell;
+use core::marker::PhantomData;
+
+/// Architecture provided critical sections.
+pub trait Atomic {
+    /// Run `f` with interrupts disabled.
+    unsafe fn atomic<F: FnOnce() -> R, R>(f: F) -> R;
+}
+
+/// A wrapping event counter.
+pub struct Counter<A: Atomic> {
+    value: VolatileCell<u32>,
+    atomic: PhantomData<A>,
+}
+
+impl<A: Atomic> Counter<A> {
+    pub const fn new() -> Counter<A> {
+        Counter {
+            value: VolatileCell::new(0),
+            atomic: PhantomData,
+        }
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, count: u32) {
+        unsafe {
+            A::atomic(|| self.value.set(self.value.get().wrapping_add(count)));
+        }
+    }
+
+    pub fn get(&self) -> u32 {
+        self.value.get()
+    }
+
+    /// Return the current count and reset it to zero in one step, so no
+    /// increments are lost between reading and resetting.
+    pub fn take(&self) -> u32 {
+        unsafe {
+            A::atomic(|| {
+                let value = self.value.get();
+                self.value.set(0);
+                value
+            })
+        }
+    }
+
+    pub fn reset(&self) {
+        self.value.set(0);
+    }
+}
+
+/// A value that is set directly, along with the highest value it has been
+/// set to.
+pub struct Gauge<A: Atomic> {
+    value: VolatileCell<u32>,
+    max: VolatileCell<u32>,
+    atomic: PhantomData<A>,
+}
+
+impl<A: Atomic> Gauge<A> {
+    pub const fn new() -> Gauge<A> {
+        Gauge {
+            value: VolatileCell::new(0),
+            max: VolatileCell::new(0),
+            atomic: PhantomData,
+        }
+    }
+
+    pub fn set(&self, value: u32) {
+        unsafe {
+            A::atomic(|| {
+                self.value.set(value);
+                if value > self.max.get() {
+                    self.max.set(value);
+                }
+            });
+        }
+    }
+
+    pub fn get(&self) -> u32 {
+        self.value.get()
+    }
+
+    /// The highest value recorded since creation or the last `reset_max()`.
+    pub fn max(&self) -> u32 {
+        self.max.get()
+    }
+
+    pub fn reset_max(&self) {
+        unsafe {
+            A::atomic(|| self.max.set(self.value.get()));
+        }
+    }
+}
+
+/// A set of up to 32 flags, where a lower index has a higher priority.
+pub struct Flags<A: Atomic> {
+    bits: VolatileCell<u32>,
+    atomic: PhantomData<A>,
+}
+
+impl<A: Atomic> Flags<A> {
+    pub const fn new() -> Flags<A> {
+        Flags {
+            bits: VolatileCell::new(0),
+            atomic: PhantomData,
+        }
+    }
+
+    pub fn set(&self, index: u8) {
+        let mask = Self::mask(index);
+        unsafe {
+            A::atomic(|| self.bits.set(self.bits.get() | mask));
+        }
+    }
+
+    pub fn clear(&self, index: u8) {
+        let mask = !Self::mask(index);
+        unsafe {
+            A::atomic(|| self.bits.set(self.bits.get() & mask));
+        }
+    }
+
+    pub fn is_set(&self, index: u8) -> bool {
+        self.bits.get() & Self::mask(index) != 0
+    }
+
+    /// The bit for flag `index`. Only indices below 32 exist.
+    fn mask(index: u8) -> u32 {
+        debug_assert!(index < 32, "flag index out of range");
+        1 << (index & 31)
+    }
+
+    /// Whether any flag is set.
+    pub fn any(&self) -> bool {
+        self.bits.get() != 0
+    }
+
+    /// The lowest index of any set flag.
+    pub fn first(&self) -> Option<u8> {
+        let bits = self.bits.get();
+        if bits == 0 {
+            None
+        } else {
+            Some(bits.trailing_zeros() as u8)
+        }
+    }
+}
@@ -60,6 +60,57 @@ macro_rules! storage_volume {
     };
 }
 
+/// Declares the process table, application memory, kernel stack and fault
+/// policy for a board from a single description.
+///
+/// ```ignore
+/// declare_app_memory! {
+///     processes: 2,
+///     app_memory: 0xA000,
+///     stack: 0x1000,
+///     fault_response: kernel::procs::FaultResponse::Panic,
+///     ram: 80 * 1024,
+/// }
+/// ```
+///
+/// This defines `PROCESSES`, `APP_MEMORY` (placed in the `.app_memory`
+/// section), `STACK_MEMORY` (placed in the `.stack_buffer` section) and
+/// `FAULT_RESPONSE`. `ram` is the size of RAM given to the kernel in the
+/// board's linker script. Compilation fails if no processes are declared,
+/// or if the application memory and stack together do not fit in RAM.
+#[macro_export]
+macro_rules! declare_app_memory {
+    (
+        processes: $procs:expr,
+        app_memory: $app_memory:expr,
+        stack: $stack:expr,
+        fault_response: $fault_response:expr,
+        ram: $ram:expr $(,)*
+    ) => {
+        // Evaluating these array lengths underflows, failing the build, if a
+        // check does not hold.
+        #[allow(dead_code)]
+        const _AT_LEAST_ONE_PROCESS: [(); 0 - !($procs > 0) as usize] = [];
+        #[allow(dead_code)]
+        const _APP_MEMORY_FITS_IN_RAM: [(); 0 - !($app_memory + $stack <= $ram) as usize] = [];
+
+        // How the kernel responds when a process faults.
+        const FAULT_RESPONSE: $crate::procs::FaultResponse = $fault_response;
+
+        static mut PROCESSES: [Option<&'static $crate::procs::ProcessType>; $procs] =
+            [None; $procs];
+
+        #[link_section = ".app_memory"]
+        static mut APP_MEMORY: [u8; $app_memory] = [0; $app_memory];
+
+        /// Dummy buffer that causes the linker to reserve enough space for the
+        /// stack.
+        #[no_mangle]
+        #[link_section = ".stack_buffer"]
+        pub static mut STACK_MEMORY: [u8; $stack] = [0; $stack];
+    };
+}
+
 /// Create an object with the given capability.
 ///
 /// ```ignore
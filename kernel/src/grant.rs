@@ -17,6 +17,7 @@ pub struct Grant<T: Default> {
 
 pub struct AppliedGrant<T> {
     appid: AppId,
+    grant_num: usize,
     grant: *mut T,
     _phantom: PhantomData<T>,
 }
@@ -27,14 +28,20 @@ impl<T> AppliedGrant<T> {
         F: FnOnce(&mut Owned<T>, &mut Allocator) -> R,
         R: Copy,
     {
-        let mut allocator = Allocator { appid: self.appid };
+        let mut allocator = Allocator {
+            appid: self.appid,
+            grant_num: self.grant_num,
+        };
         let mut root = unsafe { Owned::new(self.grant, self.appid) };
         fun(&mut root, &mut allocator)
     }
 }
 
+/// Allocates additional memory in a process's grant region. Allocations are
+/// accounted to the grant the allocator was handed out by.
 pub struct Allocator {
     appid: AppId,
+    grant_num: usize,
 }
 
 pub struct Owned<T: ?Sized> {
@@ -88,7 +95,7 @@ impl Allocator {
                 .kernel
                 .process_map_or(Err(Error::NoSuchApp), self.appid.idx(), |process| {
                     process
-                        .alloc(size_of::<T>())
+                        .grant_alloc(self.grant_num, size_of::<T>())
                         .map_or(Err(Error::OutOfMemory), |arr| {
                             let ptr = arr.as_mut_ptr() as *mut T;
                             // We use `ptr::write` to avoid `Drop`ping the uninitialized memory in
@@ -150,6 +157,7 @@ impl<T: Default> Grant<T> {
                 } else {
                     Some(AppliedGrant {
                         appid: appid,
+                        grant_num: self.grant_num,
                         grant: cntr,
                         _phantom: PhantomData,
                     })
@@ -201,16 +209,18 @@ impl<T: Default> Grant<T> {
                     // If the pointer at that location is NULL then the grant
                     // memory needs to be allocated.
                     let new_grant = if (*ctr_ptr).is_null() {
-                        process.alloc(size_of::<T>()).map(|root_arr| {
-                            let root_ptr = root_arr.as_mut_ptr() as *mut T;
-                            // Initialize the grant contents using ptr::write, to
-                            // ensure that we don't try to drop the contents of
-                            // uninitialized memory when T implements Drop.
-                            write(root_ptr, Default::default());
-                            // Record the location in the grant pointer.
-                            write_volatile(ctr_ptr, root_ptr);
-                            root_ptr
-                        })
+                        process
+                            .grant_alloc(self.grant_num, size_of::<T>())
+                            .map(|root_arr| {
+                                let root_ptr = root_arr.as_mut_ptr() as *mut T;
+                                // Initialize the grant contents using ptr::write, to
+                                // ensure that we don't try to drop the contents of
+                                // uninitialized memory when T implements Drop.
+                                write(root_ptr, Default::default());
+                                // Record the location in the grant pointer.
+                                write_volatile(ctr_ptr, root_ptr);
+                                root_ptr
+                            })
                     } else {
                         Some(*ctr_ptr)
                     };
@@ -221,7 +231,10 @@ impl<T: Default> Grant<T> {
                     new_grant.map_or(Err(Error::OutOfMemory), move |root_ptr| {
                         let root_ptr = root_ptr as *mut T;
                         let mut root = Borrowed::new(&mut *root_ptr, appid);
-                        let mut allocator = Allocator { appid: appid };
+                        let mut allocator = Allocator {
+                            appid: appid,
+                            grant_num: self.grant_num,
+                        };
                         let res = fun(&mut root, &mut allocator);
                         Ok(res)
                     })
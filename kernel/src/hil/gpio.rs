@@ -1,5 +1,7 @@
 //! Interface for direct control of GPIO pins.
 
+use returncode::ReturnCode;
+
 /// Enum for configuring any pull-up or pull-down resistors on the GPIO pin.
 #[derive(Debug)]
 pub enum InputMode {
@@ -16,10 +18,43 @@ pub enum InterruptMode {
     EitherEdge,
 }
 
+/// Enum for selecting how much current an output pin can source or sink.
+#[derive(Debug)]
+pub enum DriveStrength {
+    Low,
+    Medium,
+    High,
+}
+
+/// Enum for selecting the edge rate of an output pin.
+#[derive(Debug)]
+pub enum SlewRate {
+    Fast,
+    Reduced,
+}
+
 pub trait PinCtl {
     /// Configure whether the pin should have a pull-up or pull-down resistor or
     /// neither.
     fn set_input_mode(&self, InputMode);
+
+    /// Configure the output drive strength of the pin. Returns `ENOSUPPORT`
+    /// if the chip cannot change it, or the pin cannot drive at `strength`.
+    fn set_drive_strength(&self, _strength: DriveStrength) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Configure the output slew rate of the pin. Returns `ENOSUPPORT` if the
+    /// chip cannot change it.
+    fn set_slew_rate(&self, _rate: SlewRate) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Enable or disable input hysteresis on the pin. Returns `ENOSUPPORT` if
+    /// the chip cannot change it.
+    fn set_hysteresis(&self, _enabled: bool) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
 }
 
 /// Interface for synchronous GPIO pins.
@@ -49,6 +49,9 @@ pub enum SlaveTransmissionType {
 pub trait I2CMaster {
     fn enable(&self);
     fn disable(&self);
+    /// Write `write_len` bytes of `data`, then read `read_len` bytes back into
+    /// `data` in one transaction. The read must follow a repeated start rather
+    /// than a stop, as many devices (and 10-bit addressing) require.
     fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8);
     fn write(&self, addr: u8, data: &'static mut [u8], len: u8);
     fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8);
@@ -12,6 +12,7 @@ pub mod gpio_async;
 pub mod i2c;
 pub mod led;
 pub mod nonvolatile_storage;
+pub mod power;
 pub mod radio;
 pub mod rng;
 pub mod sensors;
@@ -0,0 +1,12 @@
+//! Interface for putting the whole system into its lowest power state.
+
+use returncode::ReturnCode;
+
+pub trait Shutdown {
+    /// Power down everything but the wake-up logic. The system only leaves
+    /// shutdown through a reset caused by a configured wake-up source, so
+    /// this only returns if shutdown could not be entered, for example
+    /// because a wake-up source is already asserted or a debugger is
+    /// keeping the chip powered.
+    fn shutdown(&self) -> ReturnCode;
+}
@@ -98,3 +98,11 @@ pub trait Timer: Time {
     /// Sets repeating timer to fire every `interval` clock-tics.
     fn repeat(&self, interval: u32);
 }
+
+/// The `BusyWait` trait models a delay that spins the CPU for a short, precise
+/// time. It is meant for bit-banged protocols whose timing is too fine for an
+/// `Alarm`, and should not be used for longer waits.
+pub trait BusyWait {
+    /// Returns after at least `us` microseconds.
+    fn delay_us(&self, us: u32);
+}
@@ -121,4 +121,51 @@ impl Introspection {
             process.debug_timeslice_expiration_count()
         })
     }
+
+    /// Returns how many grants the board has created. Grants are numbered
+    /// from zero in the order the board called `create_grant()` for them, so
+    /// the board can map a grant number back to the driver that owns it.
+    pub fn number_grants(&self, _capability: &ProcessManagementCapability) -> usize {
+        self.kernel.number_of_grants()
+    }
+
+    /// Returns how many bytes of the app's memory grant `grant_num` is using.
+    /// This includes the grant's own region and everything the driver has
+    /// allocated for the app through the grant's `Allocator`. It is zero if
+    /// the driver has never been used by the app.
+    pub fn app_grant_usage(
+        &self,
+        app: AppId,
+        grant_num: usize,
+        _capability: &ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app.idx(), |process| process.debug_grant_usage(grant_num))
+    }
+
+    /// Returns how many bytes of the app's memory all grants are using
+    /// together.
+    pub fn app_total_grant_usage(
+        &self,
+        app: AppId,
+        _capability: &ProcessManagementCapability,
+    ) -> usize {
+        let grants = self.kernel.number_of_grants();
+        self.kernel.process_map_or(0, app.idx(), |process| {
+            (0..grants).fold(0, |total, grant_num| {
+                total + process.debug_grant_usage(grant_num)
+            })
+        })
+    }
+
+    /// Returns how many bytes of the app's memory the kernel uses for its own
+    /// bookkeeping, independent of which drivers the app uses.
+    pub fn app_kernel_overhead(
+        &self,
+        app: AppId,
+        _capability: &ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app.idx(), |process| process.debug_kernel_overhead())
+    }
 }
@@ -28,6 +28,7 @@ pub mod hil;
 pub mod introspection;
 pub mod ipc;
 pub mod syscall;
+pub mod syscall_trace;
 
 mod callback;
 mod driver;
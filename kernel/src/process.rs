@@ -2,7 +2,7 @@
 
 use core::cell::Cell;
 use core::fmt::Write;
-use core::ptr::write_volatile;
+use core::ptr::{read_volatile, write_volatile};
 use core::{mem, ptr, slice, str};
 
 use callback::AppId;
@@ -196,6 +196,10 @@ pub trait ProcessType {
     /// Get a pointer to the grant pointer for this grant number.
     unsafe fn grant_ptr(&self, grant_num: usize) -> *mut *mut u8;
 
+    /// Allocate memory from the grant region on behalf of grant `grant_num`,
+    /// recording the allocation against that grant.
+    unsafe fn grant_alloc(&self, grant_num: usize, size: usize) -> Option<&mut [u8]>;
+
     // functions for processes that are architecture specific
 
     /// Get the syscall that the process called.
@@ -231,6 +235,15 @@ pub trait ProcessType {
 
     /// Returns how many times this process has exceeded its timeslice.
     fn debug_timeslice_expiration_count(&self) -> usize;
+
+    /// Returns how many bytes of this process's grant region have been
+    /// allocated for grant `grant_num`.
+    fn debug_grant_usage(&self, grant_num: usize) -> usize;
+
+    /// Returns how many bytes of this process's grant region are used by the
+    /// kernel's own per-process state (grant pointers, callback queue and
+    /// the process struct).
+    fn debug_kernel_overhead(&self) -> usize;
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -708,6 +721,14 @@ impl<S: UserspaceKernelBoundary, M: MPU> ProcessType for Process<'a, S, M> {
         (self.mem_end() as *mut *mut u8).offset(-(grant_num + 1))
     }
 
+    unsafe fn grant_alloc(&self, grant_num: usize, size: usize) -> Option<&mut [u8]> {
+        self.alloc(size).map(|arr| {
+            let usage = self.grant_usage_ptr(grant_num);
+            write_volatile(usage, read_volatile(usage) + size);
+            arr
+        })
+    }
+
     fn get_process_name(&self) -> &'static str {
         self.process_name
     }
@@ -835,6 +856,18 @@ impl<S: UserspaceKernelBoundary, M: MPU> ProcessType for Process<'a, S, M> {
             .map_or(0, |debug| debug.timeslice_expiration_count)
     }
 
+    fn debug_grant_usage(&self, grant_num: usize) -> usize {
+        if grant_num < self.kernel.number_of_grants() {
+            unsafe { read_volatile(self.grant_usage_ptr(grant_num)) }
+        } else {
+            0
+        }
+    }
+
+    fn debug_kernel_overhead(&self) -> usize {
+        self.mem_end() as usize - self.original_kernel_memory_break as usize
+    }
+
     unsafe fn fault_fmt(&self, writer: &mut Write) {
         self.syscall.fault_fmt(writer);
     }
@@ -1014,6 +1047,9 @@ impl<S: 'static + UserspaceKernelBoundary, M: 'static + MPU> Process<'a, S, M> {
             let grant_ptrs_num = kernel.get_grant_count_and_finalize();
             let grant_ptrs_offset = grant_ptrs_num * grant_ptr_size;
 
+            // Make room for a count of the bytes allocated to each grant.
+            let grant_usage_offset = grant_ptrs_num * mem::size_of::<usize>();
+
             // Allocate memory for callback ring buffer.
             let callback_size = mem::size_of::<Task>();
             let callback_len = 10;
@@ -1025,7 +1061,7 @@ impl<S: 'static + UserspaceKernelBoundary, M: 'static + MPU> Process<'a, S, M> {
             // Initial sizes of the app-owned and kernel-owned parts of process memory.
             // Provide the app with plenty of initial process accessible memory.
             let initial_kernel_memory_size =
-                grant_ptrs_offset + callbacks_offset + process_struct_offset;
+                grant_ptrs_offset + grant_usage_offset + callbacks_offset + process_struct_offset;
             let initial_app_memory_size = 3 * 1024;
 
             if min_app_ram_size < initial_app_memory_size {
@@ -1076,6 +1112,14 @@ impl<S: 'static + UserspaceKernelBoundary, M: 'static + MPU> Process<'a, S, M> {
                 *opt = ptr::null()
             }
 
+            // Followed by the grant usage counts, all starting at zero.
+            kernel_memory_break = kernel_memory_break.offset(-(grant_usage_offset as isize));
+            let usage =
+                slice::from_raw_parts_mut(kernel_memory_break as *mut usize, grant_ptrs_num);
+            for count in usage.iter_mut() {
+                *count = 0;
+            }
+
             // Now that we know we have the space we can setup the memory
             // for the callbacks.
             kernel_memory_break = kernel_memory_break.offset(-(callbacks_offset as isize));
@@ -1191,16 +1235,26 @@ impl<S: 'static + UserspaceKernelBoundary, M: 'static + MPU> Process<'a, S, M> {
             && buf_end_addr <= self.app_break.get()
     }
 
-    /// Reset all `grant_ptr`s to NULL.
+    /// Reset all `grant_ptr`s to NULL and their usage counts to zero.
     unsafe fn grant_ptrs_reset(&self) {
         let grant_ptrs_num = self.kernel.get_grant_count_and_finalize();
         for grant_num in 0..grant_ptrs_num {
+            write_volatile(self.grant_usage_ptr(grant_num), 0);
             let grant_num = grant_num as isize;
             let ctr_ptr = (self.mem_end() as *mut *mut usize).offset(-(grant_num + 1));
             write_volatile(ctr_ptr, ptr::null_mut());
         }
     }
 
+    /// Pointer to the count of bytes allocated for `grant_num`. The counts
+    /// are stored just below the grant pointers.
+    unsafe fn grant_usage_ptr(&self, grant_num: usize) -> *mut usize {
+        let grant_ptrs_num = self.kernel.get_grant_count_and_finalize() as isize;
+        let grant_num = grant_num as isize;
+        let usage_end = (self.mem_end() as *mut *mut u8).offset(-grant_ptrs_num) as *mut usize;
+        usage_end.offset(-(grant_num + 1))
+    }
+
     fn debug_set_max_stack_depth(&self) {
         self.debug.map(|debug| {
             if self.current_stack_pointer.get() < debug.min_stack_pointer {
@@ -3,9 +3,9 @@
 use core::cell::Cell;
 use core::ptr::NonNull;
 
-use callback::Callback;
+use callback::{AppId, Callback};
 use capabilities;
-use common::cells::NumericCellExt;
+use common::cells::{NumericCellExt, OptionalCell};
 use grant::Grant;
 use ipc;
 use memop;
@@ -15,6 +15,7 @@ use platform::{Chip, Platform};
 use process::{self, Task};
 use returncode::ReturnCode;
 use syscall::{ContextSwitchReason, Syscall};
+use syscall_trace::{SyscallClass, SyscallRecord, SyscallTrace};
 
 /// The time a process is permitted to run before being pre-empted
 const KERNEL_TICK_DURATION_US: u32 = 10000;
@@ -37,6 +38,8 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+    /// Where system calls are recorded, if the board has enabled tracing.
+    syscall_trace: OptionalCell<&'static SyscallTrace>,
 }
 
 impl Kernel {
@@ -46,9 +49,40 @@ impl Kernel {
             processes: processes,
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            syscall_trace: OptionalCell::empty(),
         }
     }
 
+    /// Record every system call processes make, and its result, in `trace`.
+    pub fn set_syscall_trace(
+        &self,
+        trace: &'static SyscallTrace,
+        _capability: &capabilities::ProcessManagementCapability,
+    ) {
+        self.syscall_trace.set(trace);
+    }
+
+    fn trace_syscall(
+        &self,
+        appid: AppId,
+        class: SyscallClass,
+        driver_number: usize,
+        subdriver_number: usize,
+        arg0: usize,
+        result: ReturnCode,
+    ) {
+        self.syscall_trace.map(|trace| {
+            trace.record(SyscallRecord {
+                appid: appid,
+                class: class,
+                driver_number: driver_number,
+                subdriver_number: subdriver_number,
+                arg0: arg0,
+                result: result,
+            })
+        });
+    }
+
     /// Something was scheduled for a process, so there is more work to do.
     crate fn increment_work(&self) {
         self.work.increment();
@@ -123,6 +157,12 @@ impl Kernel {
         self.processes.len()
     }
 
+    /// Return how many grants have been created so far, without finalizing
+    /// them.
+    crate fn number_of_grants(&self) -> usize {
+        self.grant_counter.get()
+    }
+
     /// Create a new grant. This is used in board initialization to setup grants
     /// that capsules use to interact with processes.
     ///
@@ -253,6 +293,14 @@ impl Kernel {
                             match process.get_syscall() {
                                 Some(Syscall::MEMOP { operand, arg0 }) => {
                                     let res = memop::memop(process, operand, arg0);
+                                    self.trace_syscall(
+                                        appid,
+                                        SyscallClass::Memop,
+                                        operand,
+                                        arg0,
+                                        0,
+                                        res,
+                                    );
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Some(Syscall::YIELD) => {
@@ -282,6 +330,14 @@ impl Kernel {
                                                 None => ReturnCode::ENODEVICE,
                                             },
                                         );
+                                    self.trace_syscall(
+                                        appid,
+                                        SyscallClass::Subscribe,
+                                        driver_number,
+                                        subdriver_number,
+                                        0,
+                                        res,
+                                    );
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Some(Syscall::COMMAND {
@@ -300,6 +356,14 @@ impl Kernel {
                                                 None => ReturnCode::ENODEVICE,
                                             },
                                         );
+                                    self.trace_syscall(
+                                        appid,
+                                        SyscallClass::Command,
+                                        driver_number,
+                                        subdriver_number,
+                                        arg0,
+                                        res,
+                                    );
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Some(Syscall::ALLOW {
@@ -321,6 +385,14 @@ impl Kernel {
                                             None => ReturnCode::ENODEVICE,
                                         }
                                     });
+                                    self.trace_syscall(
+                                        appid,
+                                        SyscallClass::Allow,
+                                        driver_number,
+                                        subdriver_number,
+                                        0,
+                                        res,
+                                    );
                                     process.set_syscall_return_value(res.into());
                                 }
                                 _ => {}
@@ -0,0 +1,135 @@
+//! Optional trace of the system calls made by processes.
+//!
+//! When a board enables tracing, the kernel records every subscribe, command,
+//! allow and memop call, along with the value returned to the process, in a
+//! fixed size buffer. Once the buffer is full the oldest record is
+//! overwritten, so the trace always holds the most recent calls. Yields are
+//! not recorded.
+//!
+//! The trace can be printed with `fmt()`, for example from the board's panic
+//! handler, to see which calls an app made and why they failed.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! let syscall_trace = static_init!(
+//!     kernel::syscall_trace::SyscallTrace,
+//!     kernel::syscall_trace::SyscallTrace::new(&mut kernel::syscall_trace::TRACE_BUF)
+//! );
+//! board_kernel.set_syscall_trace(syscall_trace, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+use core::fmt::Write;
+
+use callback::AppId;
+use common::cells::MapCell;
+use returncode::ReturnCode;
+
+pub static mut TRACE_BUF: [Option<SyscallRecord>; 32] = [None; 32];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyscallClass {
+    Subscribe,
+    Command,
+    Allow,
+    Memop,
+}
+
+/// A single traced system call.
+#[derive(Clone, Copy)]
+pub struct SyscallRecord {
+    pub appid: AppId,
+    pub class: SyscallClass,
+    /// The driver number, or the operation for a memop.
+    pub driver_number: usize,
+    /// The subscribe, command or allow number, or the argument for a memop.
+    pub subdriver_number: usize,
+    /// The first command argument, zero for other calls.
+    pub arg0: usize,
+    /// The value returned to the process.
+    pub result: ReturnCode,
+}
+
+pub struct SyscallTrace {
+    records: MapCell<&'static mut [Option<SyscallRecord>]>,
+    /// Index of the slot the next record is written to.
+    next: Cell<usize>,
+    /// Number of calls recorded since the trace was created or cleared.
+    total: Cell<usize>,
+}
+
+impl SyscallTrace {
+    pub fn new(buffer: &'static mut [Option<SyscallRecord>]) -> SyscallTrace {
+        SyscallTrace {
+            records: MapCell::new(buffer),
+            next: Cell::new(0),
+            total: Cell::new(0),
+        }
+    }
+
+    crate fn record(&self, record: SyscallRecord) {
+        self.records.map(|records| {
+            if records.is_empty() {
+                return;
+            }
+            let next = self.next.get();
+            records[next] = Some(record);
+            self.next.set((next + 1) % records.len());
+            self.total.set(self.total.get().wrapping_add(1));
+        });
+    }
+
+    /// How many calls have been recorded, including those since overwritten.
+    pub fn total(&self) -> usize {
+        self.total.get()
+    }
+
+    /// Discard all records.
+    pub fn clear(&self) {
+        self.records.map(|records| {
+            for record in records.iter_mut() {
+                *record = None;
+            }
+        });
+        self.next.set(0);
+        self.total.set(0);
+    }
+
+    /// Call `f` on each record, oldest first.
+    pub fn each<F: FnMut(&SyscallRecord)>(&self, mut f: F) {
+        let next = self.next.get();
+        self.records.map(|records| {
+            let (newer, older) = records.split_at(next);
+            for record in older.iter().chain(newer.iter()) {
+                record.as_ref().map(|record| f(record));
+            }
+        });
+    }
+
+    /// Print the trace, oldest record first.
+    pub fn fmt(&self, writer: &mut Write) {
+        let _ = writer.write_fmt(format_args!(
+            "\r\n---| Syscall Trace ({} calls) |---\r\n",
+            self.total()
+        ));
+        self.each(|record| {
+            let name = record
+                .appid
+                .kernel
+                .process_map_or("unknown", record.appid.idx(), |process| {
+                    process.get_process_name()
+                });
+            let _ = writer.write_fmt(format_args!(
+                "[{}] {:?}({:#x}, {}, {:#x}) = {:?}\r\n",
+                name,
+                record.class,
+                record.driver_number,
+                record.subdriver_number,
+                record.arg0,
+                record.result
+            ));
+        });
+    }
+}